@@ -1,4 +1,5 @@
-use structopt::StructOpt;
+use std::{path::PathBuf, str::FromStr};
+use structopt::{clap::AppSettings, StructOpt};
 
 #[derive(StructOpt)]
 #[structopt(about = "the Servo build tool")]
@@ -34,17 +35,35 @@ pub struct CommonArgs {
     pub with_frame_pointer: bool,
     #[structopt(long)]
     pub with_raqote: bool,
-    #[structopt(long)]
+    #[structopt(long, conflicts_with = "with-layout-2013")]
     pub with_layout_2020: bool,
-    #[structopt(long)]
+    #[structopt(long, conflicts_with = "with-layout-2020")]
     pub with_layout_2013: bool,
     #[structopt(long)]
     pub without_wgl: bool,
+    #[structopt(long)]
+    pub auto_bootstrap: bool,
+    #[structopt(long)]
+    pub toolchain: Option<String>,
 }
 
 #[derive(StructOpt)]
 pub enum Subcommands {
     Build(BuildArgs),
+    Cargo(CargoArgs),
+    Check(CheckArgs),
+    Clean(CleanArgs),
+    Clippy(ClippyArgs),
+    Doc(DocArgs),
+    Fmt(FmtArgs),
+    Run(RunArgs),
+    RrRecord(RrRecordArgs),
+    RrReplay(RrReplayArgs),
+    /// List the executions of Servo recorded with rr
+    RrList,
+    RrPrune(RrPruneArgs),
+    TestUnit(TestUnitArgs),
+    TestTidy(TestTidyArgs),
 }
 
 #[derive(StructOpt)]
@@ -69,5 +88,191 @@ pub struct BuildArgs {
     pub uwp: bool,
     #[structopt(long, short)]
     pub win_arm64: bool,
+    #[structopt(long)]
+    pub no_clobber: bool,
+    pub params: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Run any cargo subcommand with the same features and environment as build
+#[structopt(setting = AppSettings::TrailingVarArg, setting = AppSettings::AllowLeadingHyphen)]
+pub struct CargoArgs {
+    #[structopt(long)]
+    pub no_clobber: bool,
+    pub subcommand: String,
+    pub params: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Run "cargo check"
+pub struct CheckArgs {
+    #[structopt(long)]
+    pub no_clobber: bool,
+    pub params: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Run "cargo clippy", failing only on warnings not in the baseline
+pub struct ClippyArgs {
+    #[structopt(long)]
+    pub no_clobber: bool,
+    #[structopt(long)]
+    pub update_baseline: bool,
+    #[structopt(long)]
+    pub baseline: Option<PathBuf>,
+    pub params: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Clean the build directory
+pub struct CleanArgs {
+    #[structopt(long)]
+    pub manifest_path: Option<PathBuf>,
+    #[structopt(long)]
+    pub cache: bool,
+    #[structopt(long)]
+    pub dry_run: bool,
+    #[structopt(long, short)]
+    pub verbose: bool,
+    pub params: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Generate documentation
+pub struct DocArgs {
+    #[structopt(long)]
+    pub no_clobber: bool,
+    #[structopt(long)]
+    pub open: bool,
+    #[structopt(long)]
+    pub document_private_items: bool,
+    #[structopt(long, short)]
+    pub package: Vec<String>,
+    pub params: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Run Servo
+pub struct RunArgs {
+    #[structopt(long, short)]
+    pub release: bool,
+    #[structopt(long, short)]
+    pub dev: bool,
+    #[structopt(long)]
+    pub bin: Option<PathBuf>,
+    #[structopt(long, require_equals = true)]
+    pub debugger: Option<Option<String>>,
+    #[structopt(long, conflicts_with = "debugger")]
+    pub debugger_cmd: Option<String>,
     pub params: Vec<String>,
 }
+
+#[derive(StructOpt)]
+/// Run Servo whilst recording execution with rr
+pub struct RrRecordArgs {
+    #[structopt(long, short)]
+    pub release: bool,
+    #[structopt(long, short)]
+    pub dev: bool,
+    #[structopt(long)]
+    pub bin: Option<PathBuf>,
+    #[structopt(long)]
+    pub chaos: bool,
+    pub params: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Replay an execution of Servo that was recorded with rr
+pub struct RrReplayArgs {
+    pub trace: Option<String>,
+}
+
+#[derive(StructOpt)]
+/// Delete old executions of Servo recorded with rr
+pub struct RrPruneArgs {
+    #[structopt(long, default_value = "1")]
+    pub keep: usize,
+}
+
+#[derive(StructOpt)]
+/// Run Servo's unit tests
+pub struct TestUnitArgs {
+    #[structopt(long, short)]
+    pub package: Option<String>,
+    #[structopt(long)]
+    pub nocapture: bool,
+    #[structopt(long, possible_values = &["junit", "json"])]
+    pub format: Option<ReportFormat>,
+    #[structopt(long)]
+    pub report: Option<PathBuf>,
+    pub test_name: Vec<String>,
+}
+
+#[derive(StructOpt)]
+/// Format the Rust source files with rustfmt
+pub struct FmtArgs {
+    #[structopt(long)]
+    pub check: bool,
+    /// Only format the Rust files changed since <base>, the upstream
+    /// branch by default. Needs a nightly rustfmt to leave the modules
+    /// they declare alone.
+    #[structopt(long, value_name = "base")]
+    pub changed: Option<Option<String>>,
+}
+
+#[derive(StructOpt)]
+/// Run tidy, Servo's style checks
+pub struct TestTidyArgs {
+    #[structopt(long)]
+    pub only: Vec<String>,
+    #[structopt(long)]
+    pub fix: bool,
+    #[structopt(long, value_name = "base")]
+    pub commits: Option<Option<String>>,
+}
+
+/// The formats `test-unit` can write its results in.
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "junit" => Ok(ReportFormat::Junit),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!(
+                "Unknown format `{}`, expected `junit` or `json`.",
+                format
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debugger_name_needs_an_equals_sign() {
+        let run_args = RunArgs::from_iter(&["run", "--debugger", "https://servo.org"]);
+        assert_eq!(run_args.debugger, Some(None));
+        assert_eq!(run_args.params, ["https://servo.org"]);
+
+        let run_args = RunArgs::from_iter(&["run", "--debugger=lldb", "https://servo.org"]);
+        assert_eq!(run_args.debugger, Some(Some(String::from("lldb"))));
+        assert_eq!(run_args.params, ["https://servo.org"]);
+
+        let run_args = RunArgs::from_iter(&["run", "https://servo.org"]);
+        assert_eq!(run_args.debugger, None);
+    }
+
+    #[test]
+    fn doc_can_skip_clobbering() {
+        assert!(DocArgs::from_iter(&["doc", "--no-clobber"]).no_clobber);
+        assert!(!DocArgs::from_iter(&["doc", "-p", "script"]).no_clobber);
+    }
+}
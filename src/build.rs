@@ -1,13 +1,26 @@
-use crate::{BuildArgs, CommonArgs, Config};
+use crate::{
+    command_base::{
+        build_env, call_rustup_run, config_build_mode, dir_size, ensure_bootstrapped,
+        ensure_clobbered, format_size, get_binary_paths, get_target_dir, host_triple,
+        path_to_string, pick_target_triple, port_manifest_path, run_cargo_build_like_command,
+        set_default_compilers, set_osx_version_flag, BuildFlags,
+    },
+    BuildArgs, CleanArgs, CommonArgs, Config, MarsError,
+};
 use std::{
-    collections::HashMap,
-    fs::read_to_string,
+    fs,
     path::PathBuf,
-    process::{exit, Command},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
 };
 
 #[allow(clippy::cognitive_complexity)]
-pub fn build(repo_root: PathBuf, config: Config, build_args: BuildArgs, common_args: CommonArgs) {
+pub fn build(
+    repo_root: PathBuf,
+    mut config: Config,
+    build_args: BuildArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
     let BuildArgs {
         mut dev,
         mut release,
@@ -15,101 +28,59 @@ pub fn build(repo_root: PathBuf, config: Config, build_args: BuildArgs, common_a
         no_package,
         verbose,
         very_verbose,
-        mut uwp,
+        uwp,
         win_arm64,
+        no_clobber,
         params,
     } = build_args;
-    let CommonArgs {
-        mut target,
-        mut features,
-        mut android,
-        magicleap,
-        media_stack,
-        libsimpleservo,
-        ..
-    } = common_args;
-
+    let mut common_args = common_args;
     // Force the UWP-enabled target if the convenience UWP flags are passed.
-    if uwp && target.is_none() {
+    if uwp && common_args.target.is_none() {
         if win_arm64 {
-            target = Some(String::from("aarch64-uwp-windows-msvc"));
+            common_args.target = Some(String::from("aarch64-uwp-windows-msvc"));
         } else {
-            target = Some(String::from("x86_64-uwp-windows-msvc"));
+            common_args.target = Some(String::from("x86_64-uwp-windows-msvc"));
         }
     }
+    let auto_bootstrap = common_args.auto_bootstrap;
 
     let mut opts = params;
 
-    // TODO - translation
-    // The lines that follow were originally defined in
-    // CommandBase.pick_target_triple. For now I've inlined them.
-    if !android {
-        android = config.build.android;
-    }
-    match (&target, android) {
-        (Some(target), true) => assert!(handle_android_target(&target)),
-        (None, true) => {
-            target = Some(config.android.target.clone());
-        }
-        _ => {}
-    }
-    if magicleap && target.is_none() {
-        target = Some(String::from("aarch64-linux-android"));
-    }
-    if let (Some(target), false, false) = (&target, android, magicleap) {
-        android = handle_android_target(&target)
-    }
-
-    if !uwp {
-        uwp = if let Some(target) = &target {
-            target.contains("uwp")
-        } else {
-            false
-        }
-    }
-
-    features.append(&mut pick_media_stack(media_stack, &target));
-    let mut target_path = get_target_dir(&repo_root);
-    let mut base_path = get_target_dir(&repo_root);
-    if android {
-        target_path.push("android");
-        base_path = target_path;
-        base_path.push(target.clone().unwrap_or_default());
-    } else if magicleap {
-        target_path.push("magicleap");
-        base_path = target_path;
-        base_path.push(target.clone().unwrap_or_default());
+    let mut flags = BuildFlags::new(&mut config, common_args)?;
+    if uwp {
+        flags.uwp = true;
     }
-    let mut release_path = base_path.clone();
-    release_path.push("release");
-    release_path.push("servo");
+    let uwp = flags.uwp;
+    let android = flags.android;
+    let magicleap = flags.magicleap;
 
-    let mut dev_path = base_path;
-    dev_path.push("debug");
-    dev_path.push("servo");
+    let (release_path, dev_path) = get_binary_paths(
+        &config,
+        &flags.target,
+        android,
+        magicleap,
+        flags.libsimpleservo,
+    );
 
     if !(release || dev) {
-        if let Some(mode) = config.build.mode {
-            match mode.as_str() {
+        if let Some(mode) = config_build_mode(&config)? {
+            match mode {
                 "dev" => dev = true,
-                "release" => release = true,
-                _ => {}
+                _ => release = true,
             };
-        }
-        if release_path.exists() && !dev_path.exists() {
+        } else if release_path.exists() && !dev_path.exists() {
             release = true;
         } else if !release_path.exists() && dev_path.exists() {
             dev = true;
         } else {
-            println!("Please specify either --dev (-d) for a development build, or --release (-r) for an optimized build.");
-
-            exit(1);
+            return Err(MarsError::Config(String::from("Please specify either --dev (-d) for a development build, or --release (-r) for an optimized build.")));
         }
     }
 
     if release && dev {
-        println!("Please specify either --dev or --release.");
-        exit(1);
+        return Err(MarsError::Config(String::from(
+            "Please specify either --dev or --release.",
+        )));
     }
 
     let servo_path = if release {
@@ -131,33 +102,37 @@ pub fn build(repo_root: PathBuf, config: Config, build_args: BuildArgs, common_a
         opts.push(String::from("-vv"));
     }
 
-    let mut env = build_env(&target, true, uwp, &features);
-    ensure_bootstrapped(&target);
-    ensure_clobbered();
+    let mut env = build_env(
+        &repo_root,
+        &config,
+        &flags.target,
+        true,
+        uwp,
+        &flags.features,
+    )?;
+    ensure_bootstrapped(&repo_root, &config, &flags.target, auto_bootstrap)?;
+    ensure_clobbered(&repo_root, &config, no_clobber)?;
 
-    // TODO translation
-    // build_start = time()
+    let build_start = Instant::now();
 
     let host = host_triple();
-    let target_triple = target.clone().unwrap_or_else(|| host.clone());
+    let target_triple = flags.target.clone().unwrap_or_else(|| host.clone());
 
-    if host.contains("apple-darwin") && target_triple == host {
-        let osx_version_flag = "-mmacosx-version-min=10.10";
-        let new_val = match env.get("CXXFLAGS") {
-            Some(val) => format!("{}{}", val, osx_version_flag),
-            None => String::from(osx_version_flag),
-        };
-
-        env.insert(String::from("CXXFLAGS"), new_val);
-    }
+    set_osx_version_flag(&mut env, &target_triple);
 
     if host.contains("windows") {
+        // TODO translation
         // vs_dirs = vs_dirs()
-        unimplemented!();
+        return Err(MarsError::Config(String::from(
+            "Building on Windows is not supported yet.",
+        )));
     }
 
     if host != target_triple && target_triple.contains("windows") {
-        unimplemented!();
+        // TODO translation
+        return Err(MarsError::Config(String::from(
+            "Cross-compiling for Windows is not supported yet.",
+        )));
         // if os.environ.get('VisualStudioVersion'):
         //     print("Can't cross-compile for Windows inside of a Visual Studio shell.\n"
         //           "Please run `python mach build [arguments]` to bypass automatic "
@@ -171,7 +146,10 @@ pub fn build(repo_root: PathBuf, config: Config, build_args: BuildArgs, common_a
         // env['PKG_CONFIG_ALLOW_CROSS'] = "1"
     }
     if uwp {
-        unimplemented!();
+        // TODO translation
+        return Err(MarsError::Config(String::from(
+            "Building for UWP is not supported yet.",
+        )));
         // # Ensure libstd is ready for the new UWP target.
         // check_call(["rustup", "component", "add", "rust-src"])
         // env['RUST_SYSROOT'] = path.expanduser('~\\.xargo')
@@ -217,14 +195,20 @@ pub fn build(repo_root: PathBuf, config: Config, build_args: BuildArgs, common_a
 
     // Ensure that GStreamer libraries are accesible when linking.
     if target_triple.contains("windows") {
-        unimplemented!();
+        // TODO translation
+        return Err(MarsError::Config(String::from(
+            "Building for Windows is not supported yet.",
+        )));
         // gst_root = gstreamer_root(target_triple, env)
         // if gst_root:
         //     append_to_path_env(os.path.join(gst_root, "lib"), env, "LIB")
     }
 
     if android {
-        unimplemented!();
+        // TODO translation
+        return Err(MarsError::Config(String::from(
+            "Building for android is not supported yet.",
+        )));
         // if "ANDROID_NDK" not in env:
         //     print("Please set the ANDROID_NDK environment variable.")
         //     sys.exit(1)
@@ -543,299 +527,252 @@ pub fn build(repo_root: PathBuf, config: Config, build_args: BuildArgs, common_a
         }
     }
 
-    // TODO translation
-    // for now I've only translated the else block
-    // if sys.platform == "win32":
-    //     env.setdefault("CC", "clang-cl.exe")
-    //     env.setdefault("CXX", "clang-cl.exe")
-    //     if uwp:
-    //         env.setdefault("CFLAGS", "")
-    //         env.setdefault("CXXFLAGS", "")
-    //         env["CFLAGS"] += " -DWINAPI_FAMILY=WINAPI_FAMILY_APP"
-    //         env["CXXFLAGS"] += " -DWINAPI_FAMILY=WINAPI_FAMILY_APP"
-    // else:
-    //     env.setdefault("CC", "clang")
-    //     env.setdefault("CXX", "clang++")
-    if !env.contains_key("CC") {
-        env.insert(String::from("CC"), String::from("clang"));
-    }
-    if !env.contains_key("CXX") {
-        env.insert(String::from("CXX"), String::from("clang++"));
-    }
-    let status = run_cargo_build_like_command(
-        &repo_root,
-        "build",
-        opts,
-        env,
-        verbose,
-        target,
-        android,
-        magicleap,
-        libsimpleservo,
-        uwp,
-        features, // TODO translation **kwargs
-    );
+    set_default_compilers(&mut env);
+    let status =
+        run_cargo_build_like_command(&repo_root, &config, "build", opts, env, verbose, flags);
 
-    // elapsed = time() - build_start
     // TODO continue translation
-}
+    // mach packages android builds and copies dependencies
+    // next to the binary on windows and macOS here
 
-fn handle_android_target(target: &str) -> bool {
-    unimplemented!();
-}
-
-fn pick_media_stack(media_stack: Option<String>, target: &Option<String>) -> Vec<String> {
-    let media_stack = media_stack.unwrap_or_else(|| {
-        let use_gstreamer = match target {
-            Some(target) => {
-                let android = target.contains("arm7") && target.contains("android");
-                let x86_64 = target.contains("x86_64");
+    let elapsed = build_start.elapsed();
 
-                android || x86_64
-            }
-            None => true,
-        };
+    // Generate Desktop Notification if elapsed-time > some threshold value
+    notify_build_done(&config, elapsed, status.is_ok());
 
-        if use_gstreamer {
-            String::from("gstreamer")
+    println!(
+        "Build {} in {}",
+        if status.is_ok() {
+            "Completed"
         } else {
-            String::from("dummy")
-        }
-    });
+            "FAILED"
+        },
+        format_duration(elapsed)
+    );
 
-    vec![format!("media-{}", media_stack)]
+    status
 }
 
-fn get_target_dir(repo_root: &PathBuf) -> PathBuf {
-    // def get_target_dir(self):
-    //     if "CARGO_TARGET_DIR" in os.environ:
-    //         return os.environ["CARGO_TARGET_DIR"]
-    //     else:
-    //         return path.join(self.context.topdir, "target")
+/// Clean the build directory, and optionally the download cache
+///
+/// With `--dry-run` nothing is deleted, instead the directories that
+/// would be removed are listed along with their total size.
+pub fn clean(
+    repo_root: PathBuf,
+    mut config: Config,
+    clean_args: CleanArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let CleanArgs {
+        manifest_path,
+        cache,
+        dry_run,
+        verbose,
+        params,
+    } = clean_args;
+    let CommonArgs {
+        target,
+        android,
+        magicleap,
+        libsimpleservo,
+        auto_bootstrap,
+        ..
+    } = common_args;
 
-    // TODO translation
-    let mut path = repo_root.clone();
-    path.push("target");
+    let (target, android) = pick_target_triple(&mut config, target, android, magicleap)?;
 
-    path
-}
+    // cargo clean only removes the directory for the target if one is given
+    let mut target_dir = get_target_dir(&config);
+    if let Some(target) = &target {
+        target_dir.push(target);
+    }
+    let mut to_remove = vec![target_dir];
+    if cache {
+        to_remove.push(config.tools.cache_dir.clone());
+    }
 
-fn build_env(
-    target: &Option<String>,
-    is_build: bool,
-    uwp: bool,
-    features: &Vec<String>,
-) -> HashMap<String, String> {
-    // TODO translation
-    HashMap::new()
-}
+    if dry_run {
+        let mut total_size = 0;
+        for path in to_remove.iter().filter(|path| path.exists()) {
+            let size = dir_size(path);
+            total_size += size;
+            println!("Would remove {} ({})", path.display(), format_size(size));
+        }
+        if !params.is_empty() {
+            println!("cargo may remove less than this, depending on {:?}", params);
+        }
+        println!("Total: {}", format_size(total_size));
 
-fn ensure_clobbered() {
-    // TODO translation
-}
+        return Ok(());
+    }
+
+    ensure_bootstrapped(&repo_root, &config, &target, auto_bootstrap)?;
 
-fn ensure_bootstrapped(target: &Option<String>) {
     // TODO translation
-}
+    // mach also removes the python virtualenv here
 
-fn host_triple() -> String {
-    // os_type = host_platform()
-    // cpu_type = platform.machine().lower()
-    // if cpu_type in ["i386", "i486", "i686", "i768", "x86"]:
-    //     cpu_type = "i686"
-    // elif cpu_type in ["x86_64", "x86-64", "x64", "amd64"]:
-    //     cpu_type = "x86_64"
-    // elif cpu_type == "arm":
-    //     cpu_type = "arm"
-    // elif cpu_type == "aarch64":
-    //     cpu_type = "aarch64"
-    // else:
-    //     cpu_type = "unknown"
-
-    // return "{}-{}".format(cpu_type, os_type)
-    let cpu_type = "x86_64";
-    let os_type = "linux";
-    format!("{}-{}", cpu_type, os_type)
+    let manifest_path = match manifest_path {
+        Some(manifest_path) => path_to_string(&manifest_path),
+        None => port_manifest_path(&repo_root, android, libsimpleservo)?,
+    };
+    let mut opts = vec![
+        String::from("clean"),
+        String::from("--manifest-path"),
+        manifest_path,
+    ];
+    if let Some(target) = &target {
+        opts.push(String::from("--target"));
+        opts.push(target.clone());
+    }
+    if verbose {
+        opts.push(String::from("-v"));
+    }
+    opts.extend(params);
+
+    let env = build_env(&repo_root, &config, &target, false, false, &[])?;
+    call_rustup_run(&repo_root, &config, "cargo", opts, env, verbose)?;
+
+    if cache {
+        let cache_dir = &config.tools.cache_dir;
+        if cache_dir.exists() {
+            println!("Removing {}", cache_dir.display());
+            fs::remove_dir_all(cache_dir).map_err(|error| MarsError::Io {
+                context: format!("remove {}", cache_dir.display()),
+                error,
+            })?;
+        }
+    }
+
+    Ok(())
 }
 
-fn run_cargo_build_like_command(
-    repo_root: &PathBuf,
-    command: &str,
-    mut cargo_args: Vec<String>,
-    env: HashMap<String, String>,
-    verbose: bool,
-    target: Option<String>,
-    android: bool,
-    magicleap: bool,
-    libsimpleservo: bool,
-    uwp: bool,
-    mut features: Vec<String>,
-) {
-    // TODO translation - these used to be optional args
-    let debug_mozjs = false;
-    let with_debug_assertions = false;
-    let with_frame_pointer = false;
-    let with_raqote = false;
-    let without_wgl = false;
-    let with_layout_2020 = false;
-    let with_layout_2013 = false;
-    let media_stack = Option::<String>::None;
-    // end optional args
+/// Notify the user that the build is complete.
+///
+/// If `notify-command` is set in the `[tools]` section of `.servobuild`
+/// it is always run. Otherwise a desktop notification is shown if the
+/// build took longer than `notify-threshold` seconds.
+pub fn notify_build_done(config: &Config, elapsed: Duration, success: bool) {
+    let title = "Servo build";
+    let text = format!(
+        "{} in {}",
+        if success { "Completed" } else { "FAILED" },
+        format_duration(elapsed)
+    );
 
-    // TODO translation
-    // env = env or self.build_env()
-    // target, android = self.pick_target_triple(target, android, magicleap)
-
-    let mut args = vec![];
-    let port = if libsimpleservo || android {
-        let api = if android { "jniapi" } else { "capi" };
-        // TODO make this path join cross platform
-        format!("libsimpleservo/{}", api)
+    let result = if let Some(notify_command) = &config.tools.notify_command {
+        notify_with_command(notify_command, title, &text, elapsed, success)
+    } else if elapsed.as_secs() > config.tools.notify_threshold {
+        notify_desktop(title, &text)
     } else {
-        String::from("glutin")
+        Ok(())
     };
-    args.push(String::from("--manifest-path"));
-    let manifest_path = {
-        let mut manifest_path = repo_root.clone();
-        manifest_path.push("ports");
-        manifest_path.push(port);
-        manifest_path.push("Cargo.toml");
-        manifest_path
-            .to_str()
-            .expect("failed to convert manifest path to string")
-            .to_owned()
-    };
-    args.push(manifest_path);
 
-    if let Some(target) = target {
-        args.push(String::from("--target"));
-        args.push(target);
+    if let Err(message) = result {
+        eprintln!("[Warning] Could not generate notification! {}", message);
     }
+}
 
-    // TODO translation
-    // the existing python code had this comment which makes me think it takes features as &mut
-    // (does code beyond this method reference features list?)
-    // # If we're passed a list, mutate it even if it's empty
-
-    // TODO translation
-    // if self.config["build"]["debug-mozjs"] or debug_mozjs:
-    //     features.append("debugmozjs")
-    if !magicleap {
-        features.push(String::from("native-bluetooth"));
+/// Runs the user's notify command as `<command> <title> <text>`, with the
+/// build status and duration (in seconds) also available as the
+/// `MARS_BUILD_STATUS` and `MARS_BUILD_DURATION` environment variables.
+fn notify_with_command(
+    command: &str,
+    title: &str,
+    text: &str,
+    elapsed: Duration,
+    success: bool,
+) -> Result<(), String> {
+    let status = Command::new(command)
+        .args([title, text])
+        .env(
+            "MARS_BUILD_STATUS",
+            if success { "success" } else { "failure" },
+        )
+        .env("MARS_BUILD_DURATION", elapsed.as_secs().to_string())
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(format!("Could not run '{}'.", command)),
     }
-    // if uwp:
-    //     features.append("canvas2d-raqote")
-    //     features.append("no-wgl")
-    //     features.append("uwp")
-    // else:
-    //     # Non-UWP builds provide their own libEGL via mozangle.
-    //     the append egl below was in this else block
-    features.push(String::from("egl"));
-
-    // TODO translation
-    // I only translated the else case here
-    // if with_raqote and "canvas2d-azure" not in features:
-    //     features.append("canvas2d-raqote")
-    // elif "canvas2d-azure" not in features:
-    features.push(String::from("canvas2d-raqote"));
-
-    // TODO translation I only translated the else case
-    // if with_layout_2020 or (self.config["build"]["layout-2020"] and not with_layout_2013):
-    //     features.append("layout-2020")
-    // elif "layout-2020" not in features:
-    features.push(String::from("layout-2013"));
-
-    // TODO translation
-    // all these default to unused, so skipping them for now
-    // if with_frame_pointer:
-    //     env['RUSTFLAGS'] = env.get('RUSTFLAGS', "") + " -C force-frame-pointers=yes"
-    //     features.append("profilemozjs")
-
-    // if without_wgl:
-    //     features.append("no-wgl")
-
-    // if self.config["build"]["webgl-backtrace"]:
-    //     features.append("webgl-backtrace")
-
-    // if self.config["build"]["dom-backtrace"]:
-    //     features.append("dom-backtrace")
-
-    // if with_debug_assertions or self.config["build"]["debug-assertions"]:
-    //     env['RUSTFLAGS'] = env.get('RUSTFLAGS', "") + " -C debug_assertions"
+}
 
-    assert!(!cargo_args.contains(&String::from("--features")));
-    args.push(String::from("--features"));
-    args.push(features.join(" "));
+#[cfg(target_os = "linux")]
+fn notify_desktop(title: &str, text: &str) -> Result<(), String> {
+    let notify_send = Command::new("notify-send")
+        .args(["--hint=int:transient:1", title, text])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if let Ok(status) = notify_send {
+        if status.success() {
+            return Ok(());
+        }
+    }
 
-    // TODO translation
-    // only translated the else case here
-    // if target and 'uwp' in target:
-    //     return call(["xargo", command] + args + cargo_args, env=env, verbose=verbose)
-    // else:
-    let mut full_args = vec![String::from(command)];
-    full_args.append(&mut args);
-    full_args.append(&mut cargo_args);
-    call_rustup_run(repo_root, "cargo", full_args, env, verbose)
+    // Fall back to calling the freedesktop notification service
+    // over DBus directly, which is what mach does.
+    let gdbus = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.Notifications",
+            "--object-path",
+            "/org/freedesktop/Notifications",
+            "--method",
+            "org.freedesktop.Notifications.Notify",
+            title,
+            "0",
+            "",
+            title,
+            text,
+            "[]",
+            "{'transient': <true>}",
+            "-1",
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    match gdbus {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(String::from(
+            "Neither notify-send nor gdbus could send a desktop notification.",
+        )),
+    }
 }
 
-// TODO translation originally this used **kwargs to pass
-// arbitrary args to the call method
-fn call_rustup_run(
-    repo_root: &PathBuf,
-    command: &str,
-    mut args: Vec<String>,
-    env: HashMap<String, String>,
-    verbose: bool,
-) {
-    // BIN_SUFFIX = ".exe" if sys.platform == "win32" else ""
-    let bin_suffix = String::new();
+#[cfg(not(target_os = "linux"))]
+fn notify_desktop(_title: &str, _text: &str) -> Result<(), String> {
     // TODO translation
-    // skipping config parsing for now
-    // default config is use-rustup=true
-    let use_rustup = true;
-    let (command, args) = if use_rustup {
-        let rustup_command = "rustup";
-        let mut rustup_args = vec![
-            String::from("run"),
-            String::from("--install"),
-            rust_toolchain(repo_root),
-        ];
-        args.insert(0, String::from(command));
-        rustup_args.extend(args);
-
-        (rustup_command, rustup_args)
-    } else {
-        (command, args)
-    };
-
-    let command = format!("{}{}", command, bin_suffix);
-
-    call(command, args, env, verbose)
+    // mach also supports desktop notifications on windows and macOS
+    Ok(())
 }
 
-fn rust_toolchain(repo_root: &PathBuf) -> String {
-    // TODO translation
-    // mach caches this function call to only read the file once
-
-    let mut path = repo_root.clone();
-    path.push("rust-toolchain");
-    let toolchain = read_to_string(path)
-        .expect("Failed to read rust_toolchain file.")
-        .trim()
-        .to_owned();
-
-    // if windows
-    // toolchain += "-x86_64-pc-windows-msvc";
-    toolchain
+/// Formats a duration as `H:MM:SS`, matching the output of mach.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
 }
 
-/// Wrap std::process::Command printing the command if verbose=true.
-fn call(command: String, args: Vec<String>, env: HashMap<String, String>, verbose: bool) {
-    if verbose {
-        println!("{} {:?}", command, args);
-    };
-    // TODO translation
-    // the original code calls normalize_env here
-    // also sets shell=true for windows users in the subprocess.call
-    Command::new(command).args(args).envs(env).status();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_formatted_like_mach() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0:00:00");
+        assert_eq!(format_duration(Duration::from_millis(42_900)), "0:00:42");
+        assert_eq!(format_duration(Duration::from_secs(60)), "0:01:00");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "0:59:59");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1:00:00");
+        assert_eq!(format_duration(Duration::from_secs(90_061)), "25:01:01");
+    }
 }
@@ -0,0 +1,2414 @@
+use crate::{CommonArgs, Config, MarsError};
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, read_to_string},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+/// Resolves the target triple and whether this is an android build
+/// from the command line arguments and the config.
+pub fn pick_target_triple(
+    config: &mut Config,
+    mut target: Option<String>,
+    mut android: bool,
+    magicleap: bool,
+) -> Result<(Option<String>, bool), MarsError> {
+    if !android {
+        android = config.build.android;
+    }
+    match (&target, android) {
+        (Some(target), true) if !handle_android_target(config, target) => {
+            return Err(MarsError::Config(format!(
+                "{} is not an android target",
+                target
+            )));
+        }
+        (None, true) => {
+            target = Some(config.android.target.clone());
+        }
+        _ => {}
+    }
+    if magicleap && target.is_none() {
+        target = Some(String::from("aarch64-linux-android"));
+    }
+    if let (Some(target), false, false) = (&target, android, magicleap) {
+        android = handle_android_target(config, target)
+    }
+
+    Ok((target, android))
+}
+
+/// Sets the android section of the config for `target`, returning
+/// whether `target` is an android triple.
+pub fn handle_android_target(config: &mut Config, target: &str) -> bool {
+    let (platform, toolchain_prefix, arch, lib, toolchain_name) = match target {
+        "armv7-linux-androideabi" => (
+            "android-21",
+            "arm-linux-androideabi",
+            "arm",
+            "armeabi-v7a",
+            "arm-linux-androideabi",
+        ),
+        "aarch64-linux-android" => ("android-21", target, "arm64", "arm64-v8a", target),
+        // https://github.com/jondot/rust-cross/blob/master/README.md
+        "i686-linux-android" => ("android-18", "x86", "x86", "x86", target),
+        _ => return false,
+    };
+    config.android.platform = String::from(platform);
+    config.android.target = String::from(target);
+    config.android.toolchain_prefix = String::from(toolchain_prefix);
+    config.android.arch = String::from(arch);
+    config.android.lib = String::from(lib);
+    config.android.toolchain_name = String::from(toolchain_name);
+
+    true
+}
+
+pub fn pick_media_stack(media_stack: Option<String>, target: &Option<String>) -> Vec<String> {
+    let media_stack = media_stack.unwrap_or_else(|| {
+        let use_gstreamer = match target {
+            Some(target) => {
+                let android = target.contains("arm7") && target.contains("android");
+                let x86_64 = target.contains("x86_64");
+
+                android || x86_64
+            }
+            None => true,
+        };
+
+        if use_gstreamer {
+            String::from("gstreamer")
+        } else {
+            String::from("dummy")
+        }
+    });
+
+    vec![format!("media-{}", media_stack)]
+}
+
+/// Returns the directory cargo writes build artifacts to.
+///
+/// This is resolved when the config is loaded, see `Config::new`
+/// for the order in which the possible locations are considered.
+pub fn get_target_dir(config: &Config) -> PathBuf {
+    // def get_target_dir(self):
+    //     if "CARGO_TARGET_DIR" in os.environ:
+    //         return os.environ["CARGO_TARGET_DIR"]
+    //     else:
+    //         return path.join(self.context.topdir, "target")
+    config.tools.target_dir.clone()
+}
+
+/// Returns the `mode` from the `[build]` section of `.servobuild`,
+/// which is either `dev` or `release` if it is set.
+pub fn config_build_mode(config: &Config) -> Result<Option<&str>, MarsError> {
+    match config.build.mode.as_deref() {
+        Some(mode @ "dev") | Some(mode @ "release") => Ok(Some(mode)),
+        Some(mode) => Err(MarsError::Config(format!(
+            "Unknown build mode `{}` in .servobuild, expected `dev` or `release`.",
+            mode
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Returns the paths of the release and dev builds of servo,
+/// whether or not they exist.
+pub fn get_binary_paths(
+    config: &Config,
+    target: &Option<String>,
+    android: bool,
+    magicleap: bool,
+    mut simpleservo: bool,
+) -> (PathBuf, PathBuf) {
+    let mut base_path = get_target_dir(config);
+    // BIN_SUFFIX = ".exe" if sys.platform == "win32" else ""
+    let mut binary_name = if cfg!(windows) { "servo.exe" } else { "servo" };
+
+    if android {
+        base_path.push("android");
+        base_path.push(&config.android.target);
+        simpleservo = true;
+    } else if magicleap {
+        base_path.push("magicleap");
+        base_path.push("aarch64-linux-android");
+        binary_name = "libmlservo.a";
+    } else if let Some(target) = target {
+        base_path.push(target);
+    }
+
+    if simpleservo {
+        binary_name = if cfg!(windows) {
+            "simpleservo.dll"
+        } else if cfg!(target_os = "macos") {
+            "libsimpleservo.dylib"
+        } else {
+            "libsimpleservo.so"
+        };
+    }
+
+    let mut release_path = base_path.clone();
+    release_path.push("release");
+    release_path.push(binary_name);
+
+    let mut dev_path = base_path;
+    dev_path.push("debug");
+    dev_path.push(binary_name);
+
+    (release_path, dev_path)
+}
+
+/// Returns the path of an existing servo build, picking between the
+/// release and dev builds the same way `build` does.
+pub fn get_binary_path(
+    config: &Config,
+    mut release: bool,
+    mut dev: bool,
+    target: &Option<String>,
+    android: bool,
+    magicleap: bool,
+    simpleservo: bool,
+) -> Result<PathBuf, MarsError> {
+    let (release_path, dev_path) =
+        get_binary_paths(config, target, android, magicleap, simpleservo);
+
+    // Prefer release if both given
+    if release && dev {
+        dev = false;
+    }
+
+    let release_exists = release_path.exists();
+    let dev_exists = dev_path.exists();
+
+    if !release_exists && !dev_exists {
+        return Err(MarsError::BuildNotFound(String::from(
+            "No Servo binary found. Perhaps you forgot to run `mars build`?",
+        )));
+    }
+
+    if !(release || dev) {
+        match config_build_mode(config)? {
+            Some("dev") => dev = true,
+            Some(_) => release = true,
+            None if release_exists && dev_exists => {
+                return Err(MarsError::Config(String::from(
+                    "You have multiple profiles built. Please specify which one to run with \
+                     '--release' or '--dev'.",
+                )));
+            }
+            None if release_exists => return Ok(release_path),
+            None => return Ok(dev_path),
+        }
+    }
+
+    if release && release_exists {
+        Ok(release_path)
+    } else if dev && dev_exists {
+        Ok(dev_path)
+    } else {
+        Err(MarsError::BuildNotFound(format!(
+            "The {} profile is not built. Please run `mars build{}` and try again.",
+            if release { "release" } else { "dev" },
+            if release { " --release" } else { " --dev" }
+        )))
+    }
+}
+
+/// What `build_env` finds out by looking at the system, kept apart
+/// so the environment can be worked out without running anything.
+struct BuildEnvProbes {
+    /// The gstreamer downloaded by bootstrap, if the build needs it.
+    gstreamer_root: Option<PathBuf>,
+    /// Whether `ld.gold` is on the PATH.
+    has_gold: bool,
+    /// The short hash of HEAD and whether the tree has changes,
+    /// when building from a git checkout.
+    git: Option<(String, bool)>,
+    /// Whether `android-toolchains/sdk` exists in the repo.
+    android_sdk: bool,
+    /// Whether `android-toolchains/ndk` exists in the repo.
+    android_ndk: bool,
+    /// Whether `android-toolchains/sdk/platform-tools` exists in the repo.
+    android_platform_tools: bool,
+}
+
+/// Return an extended environment dictionary.
+///
+/// This starts from the environment mars was launched with, and adds
+/// the variables cargo needs to build Servo.
+pub fn build_env(
+    repo_root: &Path,
+    config: &Config,
+    target: &Option<String>,
+    is_build: bool,
+    uwp: bool,
+    features: &[String],
+) -> Result<HashMap<String, String>, MarsError> {
+    let host = host_triple();
+    let target_triple = target.clone().unwrap_or_else(|| host.clone());
+
+    let git = if repo_root.join(".git").is_dir() && is_build {
+        let git_sha = git_output(repo_root, &["rev-parse", "--short", "HEAD"])?;
+        let git_is_dirty = !git_output(repo_root, &["status", "--porcelain"])?.is_empty();
+
+        Some((git_sha, git_is_dirty))
+    } else {
+        None
+    };
+    let toolchains = repo_root.join("android-toolchains");
+    let probes = BuildEnvProbes {
+        gstreamer_root: needs_gstreamer_env(repo_root, &target_triple, uwp, features)?,
+        has_gold: config.tools.rustc_with_gold && is_on_path("ld.gold"),
+        git,
+        android_sdk: toolchains.join("sdk").is_dir(),
+        android_ndk: toolchains.join("ndk").is_dir(),
+        android_platform_tools: toolchains.join("sdk").join("platform-tools").is_dir(),
+    };
+
+    Ok(build_env_from(
+        env::vars().collect(),
+        repo_root,
+        config,
+        target,
+        &host,
+        &probes,
+    ))
+}
+
+/// Adds the variables cargo needs to build Servo to `env`, given what
+/// `build_env` found out about the system in `probes`.
+fn build_env_from(
+    mut env: HashMap<String, String>,
+    repo_root: &Path,
+    config: &Config,
+    target: &Option<String>,
+    host: &str,
+    probes: &BuildEnvProbes,
+) -> HashMap<String, String> {
+    let mut extra_path = vec![];
+    let mut extra_lib = vec![];
+
+    // TODO translation
+    // the msvc block which adds cmake, llvm, moztools, nuget and
+    // python to the path has not been translated
+    // if "msvc" in (target or host_triple()):
+
+    if let Some(gst_path) = &probes.gstreamer_root {
+        extra_path.push(path_to_string(&gst_path.join("bin")));
+        let lib_path = gst_path.join("lib");
+        // we append in the reverse order so that system gstreamer libraries
+        // do not get precedence
+        extra_path.insert(0, path_to_string(&lib_path));
+        extra_lib.insert(0, path_to_string(&lib_path));
+        append_to_path_env(
+            &path_to_string(&lib_path.join("pkgconfig")),
+            &mut env,
+            "PKG_CONFIG_PATH",
+        );
+    }
+
+    // TODO translation
+    // mach sets HARFBUZZ_SYS_NO_PKG_CONFIG when running on Ubuntu 16.04
+    // if distrib == "Ubuntu" and version == "16.04":
+    //     env["HARFBUZZ_SYS_NO_PKG_CONFIG"] = "true"
+
+    if !extra_path.is_empty() {
+        append_to_path_env(&extra_path.join(PATH_SEPARATOR), &mut env, "PATH");
+    }
+
+    match config.build.incremental {
+        Some(true) => {
+            env.insert(String::from("CARGO_INCREMENTAL"), String::from("1"));
+        }
+        Some(false) => {
+            env.insert(String::from("CARGO_INCREMENTAL"), String::from("0"));
+        }
+        None => {}
+    }
+
+    if !extra_lib.is_empty() {
+        let path_var = if host.contains("apple-darwin") {
+            "DYLD_LIBRARY_PATH"
+        } else {
+            "LD_LIBRARY_PATH"
+        };
+        append_to_path_env(&extra_lib.join(PATH_SEPARATOR), &mut env, path_var);
+    }
+
+    // Paths to Android build tools:
+    if let Some(sdk) = &config.android.sdk {
+        env.insert(String::from("ANDROID_SDK"), sdk.clone());
+    }
+    if let Some(ndk) = &config.android.ndk {
+        env.insert(String::from("ANDROID_NDK"), ndk.clone());
+    }
+    if let Some(toolchain) = &config.android.toolchain {
+        env.insert(String::from("ANDROID_TOOLCHAIN"), toolchain.clone());
+    }
+    env.insert(
+        String::from("ANDROID_PLATFORM"),
+        config.android.platform.clone(),
+    );
+
+    let toolchains = repo_root.join("android-toolchains");
+    for (kind, exists) in &[("sdk", probes.android_sdk), ("ndk", probes.android_ndk)] {
+        if *exists {
+            env.entry(format!("ANDROID_{}", kind.to_uppercase()))
+                .or_insert_with(|| path_to_string(&toolchains.join(kind)));
+        }
+    }
+
+    let tools = toolchains.join("sdk").join("platform-tools");
+    if probes.android_platform_tools {
+        let path = match env.get("PATH") {
+            Some(path) => format!("{}{}{}", path_to_string(&tools), PATH_SEPARATOR, path),
+            None => path_to_string(&tools),
+        };
+        env.insert(String::from("PATH"), path);
+    }
+
+    // These are set because they are the variable names that build-apk
+    // expects. However, other submodules have makefiles that reference
+    // the env var names above. Once glutin is enabled and set as the
+    // default, we could modify the subproject makefiles to use the names
+    // below and remove the vars above, to avoid duplication.
+    if let Some(sdk) = env.get("ANDROID_SDK").cloned() {
+        env.insert(String::from("ANDROID_HOME"), sdk);
+    }
+    if let Some(ndk) = env.get("ANDROID_NDK").cloned() {
+        env.insert(String::from("NDK_HOME"), ndk);
+    }
+    if let Some(toolchain) = env.get("ANDROID_TOOLCHAIN").cloned() {
+        env.insert(String::from("NDK_STANDALONE"), toolchain);
+    }
+
+    // TODO translation
+    // hosts_file_path and test_unit are not yet parameters of build_env
+    // if hosts_file_path:
+    //     env['HOST_FILE'] = hosts_file_path
+    // if not test_unit:
+    env.insert(
+        String::from("RUSTDOC"),
+        path_to_string(&repo_root.join("etc").join("rustdoc-with-private")),
+    );
+
+    if let Some(rustflags) = &config.build.rustflags {
+        append_to_rustflags(&mut env, rustflags);
+    }
+
+    // Don't run the gold linker if on Windows https://github.com/servo/servo/issues/9499
+    if config.tools.rustc_with_gold && !host.contains("windows") && probes.has_gold {
+        append_to_rustflags(&mut env, "-C link-args=-fuse-ld=gold");
+    }
+
+    if let Some(ccache) = &config.build.ccache {
+        if !ccache.is_empty() {
+            env.insert(String::from("CCACHE"), ccache.clone());
+        }
+    }
+
+    // Ensure Rust uses hard floats and SIMD on ARM devices
+    if let Some(target) = target {
+        if target.starts_with("arm") || target.starts_with("aarch64") {
+            append_to_rustflags(&mut env, "-C target-feature=+neon");
+        }
+    }
+
+    append_to_rustflags(&mut env, "-W unused-extern-crates");
+
+    let mut git_info = vec![];
+    if let Some((git_sha, git_is_dirty)) = &probes.git {
+        git_info.push(String::new());
+        git_info.push(git_sha.clone());
+        if *git_is_dirty {
+            git_info.push(String::from("dirty"));
+        }
+    }
+    env.insert(String::from("GIT_INFO"), git_info.join("-"));
+
+    // mach passes `-Z thinlto` in RUSTFLAGS, which is no longer
+    // accepted by rustc, so a profile override is used instead.
+    if config.build.thinlto {
+        env.insert(
+            String::from("CARGO_PROFILE_RELEASE_LTO"),
+            String::from("thin"),
+        );
+    }
+
+    // Work around https://github.com/servo/servo/issues/24446
+    // Splitting on whitespace normalizes leading, trailing, and double spaces
+    let rustflags = env
+        .get("RUSTFLAGS")
+        .map(|flags| flags.split_whitespace().collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    env.insert(String::from("RUSTFLAGS"), rustflags);
+
+    env.insert(
+        String::from("CARGO_HOME"),
+        path_to_string(&config.tools.cargo_home_dir),
+    );
+    env.insert(
+        String::from("CARGO_TARGET_DIR"),
+        path_to_string(&get_target_dir(config)),
+    );
+
+    env
+}
+
+/// Adds the minimum macOS version to CXXFLAGS when building for the host.
+pub fn set_osx_version_flag(env: &mut HashMap<String, String>, target_triple: &str) {
+    let host = host_triple();
+    if host.contains("apple-darwin") && target_triple == host {
+        let osx_version_flag = "-mmacosx-version-min=10.10";
+        let new_val = match env.get("CXXFLAGS") {
+            Some(val) => format!("{}{}", val, osx_version_flag),
+            None => String::from(osx_version_flag),
+        };
+
+        env.insert(String::from("CXXFLAGS"), new_val);
+    }
+}
+
+/// Defaults the C and C++ compilers to clang, unless they were
+/// already set in the environment.
+pub fn set_default_compilers(env: &mut HashMap<String, String>) {
+    // TODO translation
+    // for now I've only translated the else block
+    // if sys.platform == "win32":
+    //     env.setdefault("CC", "clang-cl.exe")
+    //     env.setdefault("CXX", "clang-cl.exe")
+    //     if uwp:
+    //         env.setdefault("CFLAGS", "")
+    //         env.setdefault("CXXFLAGS", "")
+    //         env["CFLAGS"] += " -DWINAPI_FAMILY=WINAPI_FAMILY_APP"
+    //         env["CXXFLAGS"] += " -DWINAPI_FAMILY=WINAPI_FAMILY_APP"
+    // else:
+    //     env.setdefault("CC", "clang")
+    //     env.setdefault("CXX", "clang++")
+    if !env.contains_key("CC") {
+        env.insert(String::from("CC"), String::from("clang"));
+    }
+    if !env.contains_key("CXX") {
+        env.insert(String::from("CXX"), String::from("clang++"));
+    }
+}
+
+/// Returns the total size in bytes of the files in `path`, or of `path`
+/// itself if it is a file. Symlinks are not followed.
+pub fn dir_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| dir_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Formats a number of bytes for humans, for example `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+
+    format!("{:.1} {}", size, unit)
+}
+
+#[cfg(windows)]
+const PATH_SEPARATOR: &str = ";";
+#[cfg(not(windows))]
+const PATH_SEPARATOR: &str = ":";
+
+fn append_to_path_env(string: &str, env: &mut HashMap<String, String>, name: &str) {
+    let mut variable = env.get(name).cloned().unwrap_or_default();
+    if !variable.is_empty() {
+        variable.push_str(PATH_SEPARATOR);
+    }
+    variable.push_str(string);
+
+    env.insert(String::from(name), variable);
+}
+
+pub fn append_to_rustflags(env: &mut HashMap<String, String>, flags: &str) {
+    let rustflags = env.entry(String::from("RUSTFLAGS")).or_default();
+    rustflags.push(' ');
+    rustflags.push_str(flags);
+}
+
+pub fn path_to_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Returns true if `program` can be found on the PATH, the
+/// equivalent of mach calling `which <program>`.
+pub fn is_on_path(program: &str) -> bool {
+    Command::new("which")
+        .arg(program)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn git_output(repo_root: &Path, args: &[&str]) -> Result<String, MarsError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo_root)
+        .output()
+        .map_err(|error| MarsError::Io {
+            context: format!("run git {}", args.join(" ")),
+            error,
+        })?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn gstreamer_root(repo_root: &Path, target: &str) -> Option<PathBuf> {
+    // TODO translation
+    // on windows mach looks for GSTREAMER_1_0_ROOT_<arch> in the
+    // environment, or the gstreamer msvc package
+    if target.contains("linux") && !target.contains("android") {
+        Some(
+            repo_root
+                .join("support")
+                .join("linux")
+                .join("gstreamer")
+                .join("gst"),
+        )
+    } else {
+        None
+    }
+}
+
+/// Returns true if the system has a new enough version of
+/// gstreamer, as reported by pkg-config.
+fn check_gstreamer_lib() -> bool {
+    Command::new("pkg-config")
+        .args(["--atleast-version=1.16", "gstreamer-1.0"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Returns the gstreamer root if the build needs to use the
+/// gstreamer libraries downloaded by bootstrap.
+fn needs_gstreamer_env(
+    repo_root: &Path,
+    target: &str,
+    uwp: bool,
+    features: &[String],
+) -> Result<Option<PathBuf>, MarsError> {
+    if uwp {
+        return Ok(None);
+    }
+    if features.iter().any(|feature| feature == "media-dummy") {
+        return Ok(None);
+    }
+
+    // TODO translation
+    // mach always uses the gstreamer environment on macOS and windows
+    // once it has checked the installed gstreamer version
+    if check_gstreamer_lib() {
+        return Ok(None);
+    }
+
+    let gst_path = match gstreamer_root(repo_root, target) {
+        Some(gst_path) => gst_path,
+        None => return Ok(None),
+    };
+    if gst_path.join("lib").join("libgstreamer-1.0.so.0").exists() {
+        Ok(Some(gst_path))
+    } else {
+        Err(MarsError::MissingToolchain(String::from(
+            "Your system's gstreamer libraries are out of date.\n\
+             Please run ./mach bootstrap-gstreamer",
+        )))
+    }
+}
+
+/// The files which, when changed, require the target directory to
+/// be wiped before building. A copy of each is kept in the target
+/// directory so changes can be detected on the next build.
+const CLOBBER_FILES: &[&str] = &["CLOBBER", "rust-toolchain"];
+
+/// Wipes the target directory if the `CLOBBER` or `rust-toolchain`
+/// file in the repo differs from the copy stored in the target
+/// directory by the previous build.
+///
+/// mach only does this when `AUTOCLOBBER` is set, and compares
+/// modification times rather than contents. Mars always checks,
+/// unless `no_clobber` is set.
+pub fn ensure_clobbered(
+    repo_root: &Path,
+    config: &Config,
+    no_clobber: bool,
+) -> Result<(), MarsError> {
+    let target_dir = get_target_dir(config);
+
+    let changed_files: Vec<&str> = CLOBBER_FILES
+        .iter()
+        .copied()
+        .filter(|file_name| {
+            let target_copy = target_dir.join(file_name);
+            // Without a stored copy there is nothing to compare against,
+            // which is the case for a fresh target directory.
+            target_copy.exists()
+                && read_to_string(repo_root.join(file_name)).ok()
+                    != read_to_string(target_copy).ok()
+        })
+        .collect();
+
+    if !changed_files.is_empty() {
+        for file_name in &changed_files {
+            println!(
+                "The {} file has changed since the last build in {}.",
+                file_name,
+                target_dir.display()
+            );
+        }
+
+        if no_clobber {
+            println!("Not clobbering the target directory because --no-clobber was passed.");
+            println!("If the build fails with link errors, re-run without --no-clobber.");
+
+            // Leave the stored copies alone so the next build
+            // still sees the change.
+            return Ok(());
+        }
+
+        println!("Clobbering target directory: {}", target_dir.display());
+        fs::remove_dir_all(&target_dir).map_err(|error| MarsError::Io {
+            context: format!("remove target directory {}", target_dir.display()),
+            error,
+        })?;
+    }
+
+    fs::create_dir_all(&target_dir).map_err(|error| MarsError::Io {
+        context: format!("create target directory {}", target_dir.display()),
+        error,
+    })?;
+    for file_name in CLOBBER_FILES {
+        let src = repo_root.join(file_name);
+        if src.exists() {
+            fs::copy(&src, target_dir.join(file_name)).map_err(|error| MarsError::Io {
+                context: format!("copy {} into the target directory", file_name),
+                error,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The rustup components Servo needs in addition to the default
+/// minimal profile.
+/// Where a component has been renamed, each of its names is listed. The
+/// 2020 nightlies Servo pins call `llvm-tools` `llvm-tools-preview`, and
+/// newer toolchains accept the old name too, so it comes first.
+const RUSTUP_COMPONENTS: &[&[&str]] = &[
+    &["rustc-dev"],
+    &["rust-src"],
+    &["llvm-tools-preview", "llvm-tools"],
+];
+
+/// Checks that the toolchain from the `rust-toolchain` file, along with
+/// the components and target std Servo needs, are installed.
+///
+/// Unlike mach, anything missing is only installed when `auto_bootstrap`
+/// is set. Otherwise the missing pieces are reported along with the
+/// rustup commands which would install them.
+pub fn ensure_bootstrapped(
+    repo_root: &Path,
+    config: &Config,
+    target: &Option<String>,
+    auto_bootstrap: bool,
+) -> Result<(), MarsError> {
+    // TODO translation
+    // mach dispatches to the bootstrap command to check msvc
+    // dependencies when the target platform is msvc
+    let toolchain = rust_toolchain(repo_root, config)?;
+    if !config.tools.use_rustup {
+        return check_system_toolchain(&toolchain);
+    }
+
+    ensure_rustup_version()?;
+
+    // Each missing item is a description along with the rustup
+    // arguments which would install it.
+    let mut missing: Vec<(String, Vec<String>)> = vec![];
+
+    let toolchain_installed = Command::new("rustup")
+        .args(["run", &toolchain, "rustc", "--version"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if !toolchain_installed {
+        missing.push((
+            format!("toolchain {}", toolchain),
+            vec![
+                String::from("toolchain"),
+                String::from("install"),
+                String::from("--profile"),
+                String::from("minimal"),
+                toolchain.clone(),
+            ],
+        ));
+    }
+
+    let installed_components = if toolchain_installed {
+        rustup_output(&[
+            "component",
+            "list",
+            "--installed",
+            "--toolchain",
+            &toolchain,
+        ])?
+    } else {
+        String::new()
+    };
+    // Only listed if needed, to pick which name of a component to install
+    let mut available_components = None;
+    for names in RUSTUP_COMPONENTS {
+        if find_component(&installed_components, names).is_some() {
+            continue;
+        }
+
+        if available_components.is_none() {
+            available_components = Some(if toolchain_installed {
+                rustup_output(&["component", "list", "--toolchain", &toolchain])?
+            } else {
+                String::new()
+            });
+        }
+        let component = available_components
+            .as_deref()
+            .and_then(|available| find_component(available, names))
+            .unwrap_or(names[0]);
+        missing.push((
+            format!("component {}", component),
+            vec![
+                String::from("component"),
+                String::from("add"),
+                String::from("--toolchain"),
+                toolchain.clone(),
+                String::from(component),
+            ],
+        ));
+    }
+
+    if let Some(target) = target {
+        if !target.contains("uwp") {
+            let installed_targets = if toolchain_installed {
+                rustup_output(&["target", "list", "--installed", "--toolchain", &toolchain])?
+            } else {
+                String::new()
+            };
+            if !installed_targets.lines().any(|line| line.trim() == target) {
+                missing.push((
+                    format!("std for target {}", target),
+                    vec![
+                        String::from("target"),
+                        String::from("add"),
+                        String::from("--toolchain"),
+                        toolchain.clone(),
+                        target.clone(),
+                    ],
+                ));
+            }
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if auto_bootstrap {
+        for (_, args) in missing {
+            call(String::from("rustup"), args, HashMap::new(), true)?;
+        }
+
+        Ok(())
+    } else {
+        let mut message = String::from(
+            "The Rust toolchain for this repository is not fully installed. Missing:\n",
+        );
+        for (description, _) in &missing {
+            message.push_str(&format!("    {}\n", description));
+        }
+        message.push_str("\nRun the following commands to install them, or pass --auto-bootstrap:");
+        for (_, args) in &missing {
+            message.push_str(&format!("\n    rustup {}", args.join(" ")));
+        }
+
+        Err(MarsError::MissingToolchain(message))
+    }
+}
+
+/// Returns the first of `names` in the output of `rustup component list`.
+fn find_component<'a>(component_list: &str, names: &[&'a str]) -> Option<&'a str> {
+    names.iter().copied().find(|name| {
+        // Components are listed with the host triple as a suffix, for
+        // example `rust-src-x86_64-unknown-linux-gnu (installed)`.
+        component_list.lines().any(|line| {
+            let line = line.trim();
+            line == *name || line.starts_with(&format!("{}-", name))
+        })
+    })
+}
+
+fn ensure_rustup_version() -> Result<(), MarsError> {
+    let version_line = match Command::new("rustup")
+        .arg("--version")
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(_) => {
+            return Err(MarsError::MissingToolchain(String::from(
+                "It looks like rustup is not installed. See instructions at \
+                 https://github.com/servo/servo/#setting-up-your-environment",
+            )));
+        }
+    };
+
+    let version = match parse_rustup_version(&version_line) {
+        Some(version) => version,
+        None => {
+            eprintln!(
+                "[Warning] Could not read the rustup version from `{}`, Servo requires 1.21.0 or more recent.",
+                version_line.trim()
+            );
+            return Ok(());
+        }
+    };
+    if version < (1, 21, 0) {
+        return Err(MarsError::MissingToolchain(String::from(
+            "rustup is out of date, Servo requires 1.21.0 or more recent.\n\
+             Try running 'rustup self update'.",
+        )));
+    }
+
+    Ok(())
+}
+
+/// Parses the output of `rustup --version`, which looks like
+/// `rustup 1.21.1 (7832b2ebe 2019-12-20)`.
+fn parse_rustup_version(version_line: &str) -> Option<(u32, u32, u32)> {
+    let version = version_line
+        .trim()
+        .strip_prefix("rustup ")?
+        .split_whitespace()
+        .next()?;
+    let mut parts = version.split('.').map(|part| part.parse::<u32>());
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => Some((major, minor, patch)),
+        _ => None,
+    }
+}
+
+/// Checks that the rustc found on the PATH matches `toolchain`, for
+/// use when rustup is not managing the toolchain. A mismatch is only
+/// a warning, since toolchains from the system or Nix may report
+/// versions slightly differently.
+fn check_system_toolchain(toolchain: &str) -> Result<(), MarsError> {
+    let output = Command::new("rustc")
+        .arg("--version")
+        .output()
+        .map_err(|_| {
+            MarsError::MissingToolchain(String::from(
+                "use-rustup is disabled, but rustc could not be found on the PATH.",
+            ))
+        })?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+    if !rustc_version_matches(&version, toolchain) {
+        eprintln!(
+            "[Warning] The rustc on the PATH is `{}`, but this repository expects the {} toolchain.",
+            version, toolchain
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns true if the output of `rustc --version`, which looks like
+/// `rustc 1.42.0-nightly (212b2c7da 2020-01-30)`, is from `toolchain`.
+fn rustc_version_matches(version: &str, toolchain: &str) -> bool {
+    let mut parts = version.split_whitespace().skip(1);
+    let release = match parts.next() {
+        Some(release) => release,
+        None => return false,
+    };
+    let commit_date = parts.nth(1).map(|date| date.trim_end_matches(')'));
+
+    if let Some(date) = toolchain.strip_prefix("nightly-") {
+        // A nightly is built from the last commit of the previous day,
+        // so the commit date may be one day before the nightly's date.
+        let days_before = match (commit_date.and_then(parse_date), parse_date(date)) {
+            (Some(commit_date), Some(date)) => date - commit_date,
+            _ => return false,
+        };
+
+        release.ends_with("-nightly") && (days_before == 0 || days_before == 1)
+    } else if toolchain == "nightly" {
+        release.ends_with("-nightly")
+    } else if toolchain == "beta" {
+        release.contains("-beta")
+    } else if toolchain == "stable" {
+        !release.contains('-')
+    } else {
+        // A version number such as 1.42.0, or 1.42 for the latest patch release
+        release == toolchain || release.starts_with(&format!("{}.", toolchain))
+    }
+}
+
+/// Parses a `YYYY-MM-DD` date into a number of days since the unix epoch.
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.split('-').map(|part| part.parse::<i64>());
+    let (year, month, day) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) => (year, month, day),
+        _ => return None,
+    };
+
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+pub fn rustup_output(args: &[&str]) -> Result<String, MarsError> {
+    let output = Command::new("rustup")
+        .args(args)
+        .output()
+        .map_err(|error| MarsError::Io {
+            context: format!("run rustup {}", args.join(" ")),
+            error,
+        })?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns the rust target triple of the machine mars is running on.
+///
+/// The triple is detected once and cached for the lifetime of the process.
+pub fn host_triple() -> String {
+    static HOST_TRIPLE: OnceLock<String> = OnceLock::new();
+
+    HOST_TRIPLE
+        .get_or_init(|| {
+            let os_type =
+                host_platform(&uname("-s").unwrap_or_else(|| String::from(env::consts::OS)));
+            let cpu_type =
+                host_cpu(&uname("-m").unwrap_or_else(|| String::from(env::consts::ARCH)));
+
+            format!("{}-{}", cpu_type, os_type)
+        })
+        .clone()
+}
+
+/// Maps an operating system name, as reported by `uname -s` (the
+/// equivalent of python's `platform.system()`), to the vendor and os
+/// parts of a target triple.
+fn host_platform(system: &str) -> &'static str {
+    match system.to_lowercase().as_str() {
+        "linux" => "unknown-linux-gnu",
+        "darwin" | "macos" => "apple-darwin",
+        "android" => "linux-androideabi",
+        "windows" => "pc-windows-msvc",
+        "freebsd" => "unknown-freebsd",
+        // uname on windows reports the environment it runs in,
+        // for example MINGW64_NT-10.0 or MSYS_NT-10.0
+        system if system.starts_with("mingw") || system.starts_with("msys") => "pc-windows-msvc",
+        _ => "unknown",
+    }
+}
+
+/// Maps a machine name, as reported by `uname -m` (the equivalent of
+/// python's `platform.machine()`), to the architecture part of a
+/// target triple.
+fn host_cpu(machine: &str) -> &'static str {
+    match machine.to_lowercase().as_str() {
+        "i386" | "i486" | "i686" | "i768" | "x86" => "i686",
+        "x86_64" | "x86-64" | "x64" | "amd64" => "x86_64",
+        // mach only matches "arm" here, but uname reports the
+        // version on 32 bit arm linux, for example armv7l
+        machine if machine == "arm" || machine.starts_with("armv") => "arm",
+        // macOS reports aarch64 as arm64
+        "aarch64" | "arm64" => "aarch64",
+        _ => "unknown",
+    }
+}
+
+fn uname(flag: &str) -> Option<String> {
+    let output = Command::new("uname").arg(flag).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// The target and features to build, along with the flags which change
+/// them, resolved from the `CommonArgs` of a command.
+pub struct BuildFlags {
+    pub target: Option<String>,
+    pub android: bool,
+    pub magicleap: bool,
+    pub libsimpleservo: bool,
+    pub uwp: bool,
+    pub features: Vec<String>,
+    pub debug_mozjs: bool,
+    pub with_debug_assertions: bool,
+    pub with_frame_pointer: bool,
+    pub with_layout_2020: bool,
+    pub with_layout_2013: bool,
+    pub without_wgl: bool,
+}
+
+impl BuildFlags {
+    /// Picks the target triple and media stack for `common_args`.
+    pub fn new(config: &mut Config, common_args: CommonArgs) -> Result<Self, MarsError> {
+        let CommonArgs {
+            target,
+            mut features,
+            android,
+            magicleap,
+            media_stack,
+            libsimpleservo,
+            debug_mozjs,
+            with_debug_assertions,
+            with_frame_pointer,
+            with_layout_2020,
+            with_layout_2013,
+            without_wgl,
+            ..
+        } = common_args;
+
+        let (target, android) = pick_target_triple(config, target, android, magicleap)?;
+        let uwp = target
+            .as_ref()
+            .map(|target| target.contains("uwp"))
+            .unwrap_or(false);
+        features.append(&mut pick_media_stack(media_stack, &target));
+
+        Ok(BuildFlags {
+            target,
+            android,
+            magicleap,
+            libsimpleservo,
+            uwp,
+            features,
+            debug_mozjs,
+            with_debug_assertions,
+            with_frame_pointer,
+            with_layout_2020,
+            with_layout_2013,
+            without_wgl,
+        })
+    }
+}
+
+/// Resolves the build flags for `common_args`, then bootstraps, clobbers
+/// the target directory and sets up the environment the same way `build`
+/// does, so other cargo commands share cargo's cache with `build`.
+pub fn prepare_cargo_build_like(
+    repo_root: &Path,
+    config: &mut Config,
+    common_args: CommonArgs,
+    no_clobber: bool,
+) -> Result<(BuildFlags, HashMap<String, String>), MarsError> {
+    let auto_bootstrap = common_args.auto_bootstrap;
+    let flags = BuildFlags::new(config, common_args)?;
+
+    ensure_bootstrapped(repo_root, config, &flags.target, auto_bootstrap)?;
+    ensure_clobbered(repo_root, config, no_clobber)?;
+    // mach calls build_env with is_build=False for some commands, but
+    // that changes GIT_INFO, and the compiler variables below are set by
+    // `build`, so matching `build` exactly avoids rebuilding anything
+    // `build` has built.
+    let mut env = build_env(
+        repo_root,
+        config,
+        &flags.target,
+        true,
+        flags.uwp,
+        &flags.features,
+    )?;
+    let target_triple = flags.target.clone().unwrap_or_else(host_triple);
+    set_osx_version_flag(&mut env, &target_triple);
+    set_default_compilers(&mut env);
+
+    Ok((flags, env))
+}
+
+pub fn run_cargo_build_like_command(
+    repo_root: &Path,
+    config: &Config,
+    command: &str,
+    cargo_args: Vec<String>,
+    env: HashMap<String, String>,
+    verbose: bool,
+    flags: BuildFlags,
+) -> Result<(), MarsError> {
+    let (command, args, env) =
+        cargo_build_like_command(repo_root, config, command, cargo_args, env, flags)?;
+
+    call(command, args, env, verbose)
+}
+
+/// A command, along with its arguments and the environment to run it in.
+pub type CommandLine = (String, Vec<String>, HashMap<String, String>);
+
+/// Returns the command, arguments and environment which
+/// `run_cargo_build_like_command` runs, for callers which
+/// need to read the output of cargo.
+pub fn cargo_build_like_command(
+    repo_root: &Path,
+    config: &Config,
+    command: &str,
+    mut cargo_args: Vec<String>,
+    mut env: HashMap<String, String>,
+    flags: BuildFlags,
+) -> Result<CommandLine, MarsError> {
+    let BuildFlags {
+        target,
+        android,
+        magicleap,
+        libsimpleservo,
+        uwp,
+        mut features,
+        debug_mozjs,
+        with_debug_assertions,
+        with_frame_pointer,
+        with_layout_2020,
+        with_layout_2013,
+        without_wgl,
+    } = flags;
+
+    // TODO translation
+    // env = env or self.build_env()
+    // target, android = self.pick_target_triple(target, android, magicleap)
+
+    let mut args = vec![];
+    args.push(String::from("--manifest-path"));
+    args.push(port_manifest_path(repo_root, android, libsimpleservo)?);
+
+    if let Some(target) = target {
+        args.push(String::from("--target"));
+        args.push(target);
+    }
+
+    // TODO translation
+    // the existing python code had this comment which makes me think it takes features as &mut
+    // (does code beyond this method reference features list?)
+    // # If we're passed a list, mutate it even if it's empty
+
+    // Features passed straight to cargo are merged with those below, since
+    // cargo only uses the last --features it is given. They are taken first
+    // so the features which depend on others, such as layout-2013, see them.
+    features.append(&mut take_features(&mut cargo_args));
+
+    let has_feature =
+        |features: &[String], feature: &str| features.iter().any(|f| f.as_str() == feature);
+
+    if config.build.debug_mozjs || debug_mozjs {
+        features.push(String::from("debugmozjs"));
+    }
+    if !magicleap {
+        features.push(String::from("native-bluetooth"));
+    }
+    if uwp {
+        features.push(String::from("canvas2d-raqote"));
+        features.push(String::from("no-wgl"));
+        features.push(String::from("uwp"));
+    } else {
+        // Non-UWP builds provide their own libEGL via mozangle.
+        features.push(String::from("egl"));
+    }
+
+    // Raqote is the default canvas backend, so --with-raqote changes
+    // nothing. As in mach, it is left out if azure was requested.
+    if !has_feature(&features, "canvas2d-azure") && !has_feature(&features, "canvas2d-raqote") {
+        features.push(String::from("canvas2d-raqote"));
+    }
+
+    // Either layout may be requested with a flag or a feature, which
+    // override the `layout-2020` config key.
+    let layout_2020 = with_layout_2020 || has_feature(&features, "layout-2020");
+    let layout_2013 = with_layout_2013 || has_feature(&features, "layout-2013");
+    if layout_2020 && layout_2013 {
+        return Err(MarsError::Config(String::from(
+            "Only one of layout-2020 and layout-2013 can be built.",
+        )));
+    }
+    let layout = if layout_2020 || (config.build.layout_2020 && !layout_2013) {
+        "layout-2020"
+    } else {
+        "layout-2013"
+    };
+    if !has_feature(&features, layout) {
+        features.push(String::from(layout));
+    }
+
+    if with_frame_pointer {
+        append_to_rustflags(&mut env, "-C force-frame-pointers=yes");
+        features.push(String::from("profilemozjs"));
+    }
+
+    if without_wgl {
+        features.push(String::from("no-wgl"));
+    }
+
+    if config.build.webgl_backtrace {
+        features.push(String::from("webgl-backtrace"));
+    }
+
+    if config.build.dom_backtrace {
+        features.push(String::from("dom-backtrace"));
+    }
+
+    if with_debug_assertions || config.build.debug_assertions {
+        append_to_rustflags(&mut env, "-C debug_assertions");
+    }
+
+    args.push(String::from("--features"));
+    args.push(features.join(" "));
+
+    // TODO translation
+    // only translated the else case here
+    // if target and 'uwp' in target:
+    //     return call(["xargo", command] + args + cargo_args, env=env, verbose=verbose)
+    // else:
+    let mut full_args = vec![String::from(command)];
+    full_args.append(&mut args);
+    full_args.append(&mut cargo_args);
+    let (command, args) = rustup_run_command(repo_root, config, "cargo", full_args)?;
+
+    Ok((command, args, env))
+}
+
+/// Removes `--features` and `-F` from `cargo_args`, along with their values,
+/// and returns the features they list. Arguments after `--` are left alone.
+fn take_features(cargo_args: &mut Vec<String>) -> Vec<String> {
+    let mut features = vec![];
+    let mut remaining = vec![];
+    let mut args = cargo_args.drain(..);
+    while let Some(arg) = args.next() {
+        let value = if arg == "--" {
+            remaining.push(arg);
+            remaining.extend(args.by_ref());
+            break;
+        } else if arg == "--features" || arg == "-F" {
+            args.next().unwrap_or_default()
+        } else if let Some(value) = arg.strip_prefix("--features=") {
+            String::from(value)
+        } else {
+            remaining.push(arg);
+            continue;
+        };
+        features.extend(
+            value
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|feature| !feature.is_empty())
+                .map(String::from),
+        );
+    }
+    drop(args);
+    *cargo_args = remaining;
+
+    features
+}
+
+/// Returns the path of the manifest for the port being built, which is
+/// glutin unless building libsimpleservo or for android.
+pub fn port_manifest_path(
+    repo_root: &Path,
+    android: bool,
+    libsimpleservo: bool,
+) -> Result<String, MarsError> {
+    let port = if libsimpleservo || android {
+        let api = if android { "jniapi" } else { "capi" };
+        // TODO make this path join cross platform
+        format!("libsimpleservo/{}", api)
+    } else {
+        String::from("glutin")
+    };
+    let mut manifest_path = repo_root.to_path_buf();
+    manifest_path.push("ports");
+    manifest_path.push(port);
+    manifest_path.push("Cargo.toml");
+
+    manifest_path
+        .to_str()
+        .ok_or_else(|| {
+            MarsError::Config(format!(
+                "The manifest path {} is not valid unicode.",
+                manifest_path.display()
+            ))
+        })
+        .map(String::from)
+}
+
+// TODO translation originally this used **kwargs to pass
+// arbitrary args to the call method
+pub fn call_rustup_run(
+    repo_root: &Path,
+    config: &Config,
+    command: &str,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    verbose: bool,
+) -> Result<(), MarsError> {
+    let (command, args) = rustup_run_command(repo_root, config, command, args)?;
+
+    call(command, args, env, verbose)
+}
+
+/// Returns the command and arguments which run `command` with the
+/// repo's toolchain, through `rustup run` unless use-rustup is disabled.
+pub fn rustup_run_command(
+    repo_root: &Path,
+    config: &Config,
+    command: &str,
+    mut args: Vec<String>,
+) -> Result<(String, Vec<String>), MarsError> {
+    // BIN_SUFFIX = ".exe" if sys.platform == "win32" else ""
+    let bin_suffix = String::new();
+    let (command, args) = if config.tools.use_rustup {
+        let rustup_command = "rustup";
+        let mut rustup_args = vec![
+            String::from("run"),
+            String::from("--install"),
+            rust_toolchain(repo_root, config)?,
+        ];
+        args.insert(0, String::from(command));
+        rustup_args.extend(args);
+
+        (rustup_command, rustup_args)
+    } else {
+        (command, args)
+    };
+
+    let command = format!("{}{}", command, bin_suffix);
+
+    Ok((command, args))
+}
+
+/// Returns the toolchain passed with `--toolchain`, or otherwise
+/// the toolchain named in the repo's `rust-toolchain` file.
+pub fn rust_toolchain(repo_root: &Path, config: &Config) -> Result<String, MarsError> {
+    if let Some(toolchain) = &config.tools.toolchain {
+        return Ok(toolchain.clone());
+    }
+
+    // TODO translation
+    // mach caches this function call to only read the file once
+
+    let mut path = repo_root.to_path_buf();
+    path.push("rust-toolchain");
+    let toolchain = read_to_string(&path)
+        .map_err(|error| {
+            MarsError::MissingToolchain(format!(
+                "Failed to read the rust-toolchain file at {}: {}",
+                path.display(),
+                error
+            ))
+        })?
+        .trim()
+        .to_owned();
+
+    // if windows
+    // toolchain += "-x86_64-pc-windows-msvc";
+    Ok(toolchain)
+}
+
+/// Wrap std::process::Command printing the command if verbose=true.
+///
+/// Returns an error if the command could not be run, or if it
+/// exited unsuccessfully.
+pub fn call(
+    command: String,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    verbose: bool,
+) -> Result<(), MarsError> {
+    if verbose {
+        println!("{} {:?}", command, args);
+    };
+    // TODO translation
+    // the original code calls normalize_env here
+    // also sets shell=true for windows users in the subprocess.call
+    let status = Command::new(&command)
+        .args(&args)
+        .envs(env)
+        .status()
+        .map_err(|error| MarsError::Io {
+            context: format!("run {}", command),
+            error,
+        })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(MarsError::ChildFailed {
+            command: format!("{} {}", command, args.join(" ")),
+            code: status.code(),
+        })
+    }
+}
+
+/// Like `call`, but passes each line the command writes to `on_line`
+/// instead of letting the child write to the terminal.
+///
+/// stdout and stderr are read from the same pipe, so lines from the two
+/// are seen in the order the child wrote them.
+pub fn call_with_lines(
+    command: String,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    verbose: bool,
+    mut on_line: impl FnMut(&str),
+) -> Result<(), MarsError> {
+    if verbose {
+        println!("{} {:?}", command, args);
+    };
+    let io_error = |context: &str| {
+        let context = format!("{} {}", context, command);
+        move |error| MarsError::Io { context, error }
+    };
+    let (reader, writer) = io::pipe().map_err(io_error("create a pipe for"))?;
+    let mut child = {
+        // The command holds the write end of the pipe, it must be dropped
+        // once the child is spawned for the read end to see the end of
+        // the output.
+        let mut child_command = Command::new(&command);
+        child_command
+            .args(&args)
+            .envs(env)
+            .stdout(writer.try_clone().map_err(io_error("create a pipe for"))?)
+            .stderr(writer);
+        child_command.spawn().map_err(io_error("run"))?
+    };
+
+    for line in BufReader::new(reader).lines() {
+        match line {
+            Ok(line) => on_line(&line),
+            Err(_) => break,
+        }
+    }
+
+    let status = child.wait().map_err(io_error("wait for"))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(MarsError::ChildFailed {
+            command: format!("{} {}", command, args.join(" ")),
+            code: status.code(),
+        })
+    }
+}
+
+/// Returns the branch the current branch tracks, or `origin/master`
+/// if it doesn't track one, for commands which compare against a git base.
+pub fn default_git_base(repo_root: &Path) -> String {
+    let upstream = Command::new("git")
+        .args([
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ])
+        .current_dir(repo_root)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from(String::from_utf8_lossy(&output.stdout).trim()));
+
+    upstream.unwrap_or_else(|| String::from("origin/master"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A host, a target, what was probed and the expected changes to the env.
+    type EnvCase<'a> = (
+        &'a str,
+        Option<&'a str>,
+        BuildEnvProbes,
+        Vec<(&'a str, Option<&'a str>)>,
+    );
+
+    fn no_probes() -> BuildEnvProbes {
+        BuildEnvProbes {
+            gstreamer_root: None,
+            has_gold: false,
+            git: None,
+            android_sdk: false,
+            android_ndk: false,
+            android_platform_tools: false,
+        }
+    }
+
+    /// The environment `build_env_from` is expected to return, starting
+    /// from just `PATH=/usr/bin`, with `changes` made to it. A change
+    /// with no value removes the variable.
+    fn expected_env(changes: &[(&str, Option<&str>)]) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = [
+            ("PATH", "/usr/bin"),
+            ("ANDROID_PLATFORM", "android-21"),
+            ("RUSTDOC", "/servo/etc/rustdoc-with-private"),
+            ("RUSTFLAGS", "-W unused-extern-crates"),
+            ("GIT_INFO", ""),
+            ("CARGO_HOME", "/servo/.cargo"),
+            ("CARGO_TARGET_DIR", "/servo/target"),
+        ]
+        .iter()
+        .map(|(name, value)| (String::from(*name), String::from(*value)))
+        .collect();
+        for (name, value) in changes {
+            match value {
+                Some(value) => env.insert(String::from(*name), String::from(*value)),
+                None => env.remove(*name),
+            };
+        }
+
+        env
+    }
+
+    #[test]
+    fn build_env_for_each_host_and_target() {
+        let gstreamer = || BuildEnvProbes {
+            gstreamer_root: Some(PathBuf::from("/gst")),
+            ..no_probes()
+        };
+        let gold = || BuildEnvProbes {
+            has_gold: true,
+            ..no_probes()
+        };
+        let cases: Vec<EnvCase> = vec![
+            ("x86_64-unknown-linux-gnu", None, no_probes(), vec![]),
+            ("aarch64-unknown-linux-gnu", None, no_probes(), vec![]),
+            ("x86_64-apple-darwin", None, no_probes(), vec![]),
+            (
+                "x86_64-unknown-linux-gnu",
+                None,
+                gstreamer(),
+                vec![
+                    ("PATH", Some("/usr/bin:/gst/lib:/gst/bin")),
+                    ("PKG_CONFIG_PATH", Some("/gst/lib/pkgconfig")),
+                    ("LD_LIBRARY_PATH", Some("/gst/lib")),
+                ],
+            ),
+            (
+                "x86_64-apple-darwin",
+                None,
+                gstreamer(),
+                vec![
+                    ("PATH", Some("/usr/bin:/gst/lib:/gst/bin")),
+                    ("PKG_CONFIG_PATH", Some("/gst/lib/pkgconfig")),
+                    ("DYLD_LIBRARY_PATH", Some("/gst/lib")),
+                ],
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                None,
+                gold(),
+                vec![(
+                    "RUSTFLAGS",
+                    Some("-C link-args=-fuse-ld=gold -W unused-extern-crates"),
+                )],
+            ),
+            ("x86_64-pc-windows-msvc", None, gold(), vec![]),
+            (
+                "x86_64-unknown-linux-gnu",
+                Some("aarch64-linux-android"),
+                no_probes(),
+                vec![(
+                    "RUSTFLAGS",
+                    Some("-C target-feature=+neon -W unused-extern-crates"),
+                )],
+            ),
+            (
+                "x86_64-apple-darwin",
+                Some("armv7-linux-androideabi"),
+                no_probes(),
+                vec![(
+                    "RUSTFLAGS",
+                    Some("-C target-feature=+neon -W unused-extern-crates"),
+                )],
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                Some("x86_64-unknown-linux-gnu"),
+                no_probes(),
+                vec![],
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                None,
+                BuildEnvProbes {
+                    git: Some((String::from("abc1234"), true)),
+                    ..no_probes()
+                },
+                vec![("GIT_INFO", Some("-abc1234-dirty"))],
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                Some("armv7-linux-androideabi"),
+                BuildEnvProbes {
+                    android_sdk: true,
+                    android_ndk: true,
+                    android_platform_tools: true,
+                    ..no_probes()
+                },
+                vec![
+                    (
+                        "PATH",
+                        Some("/servo/android-toolchains/sdk/platform-tools:/usr/bin"),
+                    ),
+                    ("ANDROID_SDK", Some("/servo/android-toolchains/sdk")),
+                    ("ANDROID_HOME", Some("/servo/android-toolchains/sdk")),
+                    ("ANDROID_NDK", Some("/servo/android-toolchains/ndk")),
+                    ("NDK_HOME", Some("/servo/android-toolchains/ndk")),
+                    (
+                        "RUSTFLAGS",
+                        Some("-C target-feature=+neon -W unused-extern-crates"),
+                    ),
+                ],
+            ),
+        ];
+
+        let repo_root = Path::new("/servo");
+        let config = Config::from_toml(repo_root, "");
+        for (host, target, probes, changes) in cases {
+            let base_env = expected_env(&[])
+                .into_iter()
+                .filter(|(name, _)| name == "PATH")
+                .collect();
+            let target = target.map(String::from);
+            let env = build_env_from(base_env, repo_root, &config, &target, host, &probes);
+            assert_eq!(
+                env,
+                expected_env(&changes),
+                "host {} target {:?}",
+                host,
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn build_env_applies_the_config() {
+        let repo_root = Path::new("/servo");
+        let config = Config::from_toml(
+            repo_root,
+            r#"
+            [android]
+            sdk = "/sdk"
+            ndk = "/ndk"
+            toolchain = "/toolchain"
+
+            [build]
+            ccache = "sccache"
+            incremental = false
+            thinlto = true
+            "#,
+        );
+        let env = build_env_from(
+            HashMap::new(),
+            repo_root,
+            &config,
+            &None,
+            "x86_64-unknown-linux-gnu",
+            &no_probes(),
+        );
+
+        let expected = expected_env(&[
+            ("PATH", None),
+            ("ANDROID_SDK", Some("/sdk")),
+            ("ANDROID_HOME", Some("/sdk")),
+            ("ANDROID_NDK", Some("/ndk")),
+            ("NDK_HOME", Some("/ndk")),
+            ("ANDROID_TOOLCHAIN", Some("/toolchain")),
+            ("NDK_STANDALONE", Some("/toolchain")),
+            ("CCACHE", Some("sccache")),
+            ("CARGO_INCREMENTAL", Some("0")),
+            ("CARGO_PROFILE_RELEASE_LTO", Some("thin")),
+        ]);
+        assert_eq!(env, expected);
+    }
+
+    #[test]
+    fn build_env_keeps_the_cargo_home() {
+        let repo_root = Path::new("/servo");
+        let env = [
+            ("CARGO_HOME", "/home/servo/.cargo"),
+            ("SERVO_CACHE_DIR", "/cache"),
+        ];
+        let config = Config::from_toml_and_env(repo_root, "", &env);
+        let base_env = env
+            .iter()
+            .map(|(name, value)| (String::from(*name), String::from(*value)))
+            .collect();
+        let env = build_env_from(
+            base_env,
+            repo_root,
+            &config,
+            &None,
+            "x86_64-unknown-linux-gnu",
+            &no_probes(),
+        );
+        assert_eq!(env["CARGO_HOME"], "/home/servo/.cargo");
+
+        let config = Config::from_toml_and_env(
+            repo_root,
+            "[tools]\ncargo-home-dir = \"/configured\"",
+            &[("CARGO_HOME", "/home/servo/.cargo")],
+        );
+        let env = build_env_from(
+            HashMap::new(),
+            repo_root,
+            &config,
+            &None,
+            "x86_64-unknown-linux-gnu",
+            &no_probes(),
+        );
+        assert_eq!(env["CARGO_HOME"], "/configured");
+    }
+
+    #[test]
+    fn llvm_tools_is_found_under_either_name() {
+        let names = &["llvm-tools-preview", "llvm-tools"];
+        let old = "llvm-tools-preview-x86_64-unknown-linux-gnu (installed)\n\
+                   rust-src (installed)";
+        let new = "llvm-tools-x86_64-unknown-linux-gnu\nrust-src";
+
+        assert_eq!(find_component(old, names), Some("llvm-tools-preview"));
+        assert_eq!(find_component(new, names), Some("llvm-tools"));
+        assert_eq!(find_component("rust-src", names), None);
+    }
+
+    #[test]
+    fn unparseable_rustup_versions() {
+        assert_eq!(
+            parse_rustup_version("rustup 1.21.1 (7832b2ebe 2019-12-20)"),
+            Some((1, 21, 1))
+        );
+        assert_eq!(parse_rustup_version("rustup-init 1.21.1"), None);
+        assert_eq!(parse_rustup_version("rustup 1.21"), None);
+    }
+
+    #[test]
+    fn host_triples() {
+        let cpus = [
+            ("i686", "i686"),
+            ("x86_64", "x86_64"),
+            ("AMD64", "x86_64"),
+            ("arm", "arm"),
+            ("armv7l", "arm"),
+            ("aarch64", "aarch64"),
+            ("arm64", "aarch64"),
+        ];
+        let platforms = [
+            ("Linux", "unknown-linux-gnu"),
+            ("Darwin", "apple-darwin"),
+            ("Windows", "pc-windows-msvc"),
+            ("MINGW64_NT-10.0", "pc-windows-msvc"),
+        ];
+
+        for (machine, cpu) in &cpus {
+            for (system, platform) in &platforms {
+                assert_eq!(
+                    format!("{}-{}", host_cpu(machine), host_platform(system)),
+                    format!("{}-{}", cpu, platform),
+                    "uname -m {} and uname -s {}",
+                    machine,
+                    system
+                );
+            }
+        }
+        assert_eq!(host_cpu("sparc"), "unknown");
+        assert_eq!(host_platform("Plan9"), "unknown");
+    }
+
+    fn no_flags() -> BuildFlags {
+        BuildFlags {
+            target: None,
+            android: false,
+            magicleap: false,
+            libsimpleservo: false,
+            uwp: false,
+            features: vec![],
+            debug_mozjs: false,
+            with_debug_assertions: false,
+            with_frame_pointer: false,
+            with_layout_2020: false,
+            with_layout_2013: false,
+            without_wgl: false,
+        }
+    }
+
+    /// Returns the features `cargo_build_like_command` passes to cargo
+    /// for `flags`, along with the RUSTFLAGS it adds to `build_env`'s.
+    fn features_and_rustflags(
+        config: &str,
+        flags: BuildFlags,
+        cargo_args: &[&str],
+    ) -> (Vec<String>, String) {
+        let repo_root = Path::new("/servo");
+        let config = Config::from_toml(
+            repo_root,
+            &format!("[tools]\nuse-rustup = false\n{}", config),
+        );
+        let cargo_args = cargo_args.iter().map(|arg| String::from(*arg)).collect();
+        let env = [(
+            String::from("RUSTFLAGS"),
+            String::from("-W unused-extern-crates"),
+        )]
+        .iter()
+        .cloned()
+        .collect();
+        let (command, args, env) =
+            cargo_build_like_command(repo_root, &config, "build", cargo_args, env, flags)
+                .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(command, "cargo");
+
+        let features = args
+            .iter()
+            .skip_while(|arg| *arg != "--features")
+            .nth(1)
+            .expect("--features is always passed")
+            .split(' ')
+            .map(String::from)
+            .collect();
+        let rustflags = env["RUSTFLAGS"]
+            .strip_prefix("-W unused-extern-crates")
+            .expect("build_env's RUSTFLAGS come first")
+            .trim()
+            .to_string();
+        (features, rustflags)
+    }
+
+    #[test]
+    fn build_flags_features_and_rustflags() {
+        let default_features = ["native-bluetooth", "egl", "canvas2d-raqote", "layout-2013"];
+        let cases: Vec<(BuildFlags, Vec<&str>, &str)> = vec![
+            (no_flags(), default_features.to_vec(), ""),
+            (
+                BuildFlags {
+                    debug_mozjs: true,
+                    ..no_flags()
+                },
+                vec![
+                    "debugmozjs",
+                    "native-bluetooth",
+                    "egl",
+                    "canvas2d-raqote",
+                    "layout-2013",
+                ],
+                "",
+            ),
+            (
+                BuildFlags {
+                    with_frame_pointer: true,
+                    ..no_flags()
+                },
+                vec![
+                    "native-bluetooth",
+                    "egl",
+                    "canvas2d-raqote",
+                    "layout-2013",
+                    "profilemozjs",
+                ],
+                "-C force-frame-pointers=yes",
+            ),
+            (
+                BuildFlags {
+                    without_wgl: true,
+                    ..no_flags()
+                },
+                vec![
+                    "native-bluetooth",
+                    "egl",
+                    "canvas2d-raqote",
+                    "layout-2013",
+                    "no-wgl",
+                ],
+                "",
+            ),
+            (
+                BuildFlags {
+                    with_layout_2020: true,
+                    ..no_flags()
+                },
+                vec!["native-bluetooth", "egl", "canvas2d-raqote", "layout-2020"],
+                "",
+            ),
+            (
+                BuildFlags {
+                    with_layout_2013: true,
+                    ..no_flags()
+                },
+                default_features.to_vec(),
+                "",
+            ),
+            (
+                BuildFlags {
+                    features: vec![String::from("canvas2d-azure")],
+                    ..no_flags()
+                },
+                vec!["canvas2d-azure", "native-bluetooth", "egl", "layout-2013"],
+                "",
+            ),
+            (
+                BuildFlags {
+                    uwp: true,
+                    ..no_flags()
+                },
+                vec![
+                    "native-bluetooth",
+                    "canvas2d-raqote",
+                    "no-wgl",
+                    "uwp",
+                    "layout-2013",
+                ],
+                "",
+            ),
+            (
+                BuildFlags {
+                    magicleap: true,
+                    ..no_flags()
+                },
+                vec!["egl", "canvas2d-raqote", "layout-2013"],
+                "",
+            ),
+            (
+                BuildFlags {
+                    with_debug_assertions: true,
+                    ..no_flags()
+                },
+                default_features.to_vec(),
+                "-C debug_assertions",
+            ),
+            (
+                BuildFlags {
+                    with_frame_pointer: true,
+                    with_debug_assertions: true,
+                    ..no_flags()
+                },
+                vec![
+                    "native-bluetooth",
+                    "egl",
+                    "canvas2d-raqote",
+                    "layout-2013",
+                    "profilemozjs",
+                ],
+                "-C force-frame-pointers=yes -C debug_assertions",
+            ),
+        ];
+
+        for (flags, features, rustflags) in cases {
+            let (actual_features, actual_rustflags) = features_and_rustflags("", flags, &[]);
+            assert_eq!(actual_features, features);
+            assert_eq!(actual_rustflags, rustflags);
+        }
+    }
+
+    #[test]
+    fn conflicting_layouts_are_an_error() {
+        let repo_root = Path::new("/servo");
+        let config = Config::from_toml(repo_root, "[tools]\nuse-rustup = false");
+        let build = |flags: BuildFlags, cargo_args: &[&str]| {
+            let cargo_args = cargo_args.iter().map(|arg| String::from(*arg)).collect();
+            cargo_build_like_command(
+                repo_root,
+                &config,
+                "build",
+                cargo_args,
+                HashMap::new(),
+                flags,
+            )
+            .err()
+            .map(|error| error.to_string())
+        };
+        let conflict = Some(String::from(
+            "Only one of layout-2020 and layout-2013 can be built.",
+        ));
+
+        let with_layout_2013 = || BuildFlags {
+            with_layout_2013: true,
+            ..no_flags()
+        };
+        assert_eq!(
+            build(with_layout_2013(), &["--features", "layout-2020"]),
+            conflict
+        );
+        let with_layout_2020 = BuildFlags {
+            with_layout_2020: true,
+            ..no_flags()
+        };
+        assert_eq!(
+            build(with_layout_2020, &["--features", "layout-2013"]),
+            conflict
+        );
+        assert_eq!(
+            build(no_flags(), &["--features", "layout-2013 layout-2020"]),
+            conflict
+        );
+        assert_eq!(
+            build(with_layout_2013(), &["--features", "layout-2013"]),
+            None
+        );
+    }
+
+    #[test]
+    fn build_config_and_flag_precedence() {
+        let layout = |features: Vec<String>| {
+            features
+                .into_iter()
+                .filter(|feature| feature.starts_with("layout-"))
+                .collect::<Vec<_>>()
+        };
+        let layout_2020 = "[build]\nlayout-2020 = true";
+        let (features, _) = features_and_rustflags(layout_2020, no_flags(), &[]);
+        assert_eq!(layout(features), ["layout-2020"]);
+        let with_layout_2013 = BuildFlags {
+            with_layout_2013: true,
+            ..no_flags()
+        };
+        let (features, _) = features_and_rustflags(layout_2020, with_layout_2013, &[]);
+        assert_eq!(layout(features), ["layout-2013"]);
+        let with_layout_2020 = BuildFlags {
+            with_layout_2020: true,
+            ..no_flags()
+        };
+        let (features, _) =
+            features_and_rustflags("[build]\nlayout-2020 = false", with_layout_2020, &[]);
+        assert_eq!(layout(features), ["layout-2020"]);
+        let (features, _) = features_and_rustflags(layout_2020, no_flags(), &["-F", "layout-2013"]);
+        assert_eq!(layout(features), ["layout-2013"]);
+
+        // Either the key or the flag enables these
+        let config = "[build]\ndebug-mozjs = true\ndebug-assertions = true";
+        let both = BuildFlags {
+            debug_mozjs: true,
+            with_debug_assertions: true,
+            ..no_flags()
+        };
+        for (config, flags) in [(config, no_flags()), ("", both)] {
+            let (features, rustflags) = features_and_rustflags(config, flags, &[]);
+            assert_eq!(features[0], "debugmozjs");
+            assert_eq!(rustflags, "-C debug_assertions");
+        }
+
+        let backtraces = "[build]\nwebgl-backtrace = true\ndom-backtrace = true";
+        let (features, _) = features_and_rustflags(backtraces, no_flags(), &[]);
+        assert!(features.ends_with(&[
+            String::from("webgl-backtrace"),
+            String::from("dom-backtrace")
+        ]));
+    }
+
+    #[test]
+    fn build_config_incremental_and_thinlto() {
+        let repo_root = Path::new("/servo");
+        let incremental_env = || {
+            [(String::from("CARGO_INCREMENTAL"), String::from("1"))]
+                .iter()
+                .cloned()
+                .collect::<HashMap<_, _>>()
+        };
+        let cases = [
+            ("", Some("1"), None),
+            ("[build]\nincremental = false", Some("0"), None),
+            ("[build]\nincremental = true", Some("1"), None),
+            ("[build]\nthinlto = false", Some("1"), None),
+            ("[build]\nthinlto = true", Some("1"), Some("thin")),
+        ];
+        for (contents, incremental, lto) in &cases {
+            let config = Config::from_toml(repo_root, contents);
+            let env = build_env_from(
+                incremental_env(),
+                repo_root,
+                &config,
+                &None,
+                "x86_64-unknown-linux-gnu",
+                &no_probes(),
+            );
+            assert_eq!(
+                env.get("CARGO_INCREMENTAL").map(String::as_str),
+                *incremental,
+                "{}",
+                contents
+            );
+            assert_eq!(
+                env.get("CARGO_PROFILE_RELEASE_LTO").map(String::as_str),
+                *lto,
+                "{}",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn rustflags_order() {
+        let repo_root = Path::new("/servo");
+        let config = Config::from_toml(
+            repo_root,
+            "[tools]\nuse-rustup = false\n[build]\nrustflags = \"-C opt-level=2\"",
+        );
+        let env = [(String::from("RUSTFLAGS"), String::from("-C opt-level=1"))]
+            .iter()
+            .cloned()
+            .collect();
+        let target = Some(String::from("aarch64-unknown-linux-gnu"));
+        let env = build_env_from(
+            env,
+            repo_root,
+            &config,
+            &target,
+            "x86_64-unknown-linux-gnu",
+            &no_probes(),
+        );
+        let flags = BuildFlags {
+            target,
+            with_frame_pointer: true,
+            with_debug_assertions: true,
+            ..no_flags()
+        };
+        let (_, _, env) = cargo_build_like_command(repo_root, &config, "build", vec![], env, flags)
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        assert_eq!(
+            env["RUSTFLAGS"],
+            "-C opt-level=1 -C opt-level=2 -C target-feature=+neon -W unused-extern-crates \
+             -C force-frame-pointers=yes -C debug_assertions"
+        );
+    }
+
+    #[test]
+    fn rustc_versions_match_their_toolchain() {
+        let nightly = "rustc 1.42.0-nightly (212b2c7da 2020-01-30)";
+        assert!(rustc_version_matches(nightly, "nightly-2020-01-30"));
+        assert!(rustc_version_matches(nightly, "nightly-2020-01-31"));
+        assert!(rustc_version_matches(nightly, "nightly"));
+        assert!(rustc_version_matches(
+            "rustc 1.42.0-nightly (212b2c7da 2020-01-31)",
+            "nightly-2020-02-01"
+        ));
+
+        let stable = "rustc 1.41.1 (f3e1a954d 2020-02-24)";
+        assert!(rustc_version_matches(stable, "stable"));
+        assert!(rustc_version_matches(stable, "1.41.1"));
+        assert!(rustc_version_matches(stable, "1.41"));
+        assert!(rustc_version_matches(
+            "rustc 1.42.0-beta.3 (4e1c5f0e9 2020-02-28)",
+            "beta"
+        ));
+    }
+
+    #[test]
+    fn rustc_versions_from_other_toolchains_do_not_match() {
+        let nightly = "rustc 1.42.0-nightly (212b2c7da 2020-01-30)";
+        assert!(!rustc_version_matches(nightly, "nightly-2020-01-29"));
+        assert!(!rustc_version_matches(nightly, "nightly-2020-02-01"));
+        assert!(!rustc_version_matches(nightly, "stable"));
+        assert!(!rustc_version_matches(nightly, "beta"));
+
+        let stable = "rustc 1.41.1 (f3e1a954d 2020-02-24)";
+        assert!(!rustc_version_matches(stable, "nightly"));
+        assert!(!rustc_version_matches(stable, "nightly-2020-02-24"));
+        assert!(!rustc_version_matches(stable, "1.41.0"));
+        assert!(!rustc_version_matches(stable, "1.4"));
+    }
+
+    #[test]
+    fn rustc_versions_without_a_commit() {
+        // rustc built outside of a git checkout has no commit hash or date
+        assert!(rustc_version_matches("rustc 1.42.0", "1.42.0"));
+        assert!(rustc_version_matches("rustc 1.42.0", "stable"));
+        assert!(rustc_version_matches("rustc 1.42.0-nightly", "nightly"));
+        assert!(!rustc_version_matches(
+            "rustc 1.42.0-nightly",
+            "nightly-2020-01-30"
+        ));
+        assert!(!rustc_version_matches("", "stable"));
+    }
+
+    #[test]
+    fn android_targets_set_the_android_config() {
+        let android_config = |config: &Config| {
+            let android = &config.android;
+            [
+                android.platform.clone(),
+                android.target.clone(),
+                android.toolchain_prefix.clone(),
+                android.arch.clone(),
+                android.lib.clone(),
+                android.toolchain_name.clone(),
+            ]
+        };
+        let cases = [
+            (
+                "armv7-linux-androideabi",
+                [
+                    "android-21",
+                    "armv7-linux-androideabi",
+                    "arm-linux-androideabi",
+                    "arm",
+                    "armeabi-v7a",
+                    "arm-linux-androideabi",
+                ],
+            ),
+            (
+                "aarch64-linux-android",
+                [
+                    "android-21",
+                    "aarch64-linux-android",
+                    "aarch64-linux-android",
+                    "arm64",
+                    "arm64-v8a",
+                    "aarch64-linux-android",
+                ],
+            ),
+            (
+                "i686-linux-android",
+                [
+                    "android-18",
+                    "i686-linux-android",
+                    "x86",
+                    "x86",
+                    "x86",
+                    "i686-linux-android",
+                ],
+            ),
+        ];
+        for (triple, expected) in &cases {
+            for &android in &[false, true] {
+                let mut config = Config::from_toml(Path::new("/servo"), "");
+                let picked =
+                    pick_target_triple(&mut config, Some(String::from(*triple)), android, false)
+                        .unwrap_or_else(|error| panic!("{}", error));
+                assert_eq!(picked, (Some(String::from(*triple)), true));
+                assert_eq!(android_config(&config), *expected);
+            }
+        }
+
+        let mut config = Config::from_toml(Path::new("/servo"), "");
+        let defaults = android_config(&config);
+        let picked = pick_target_triple(
+            &mut config,
+            Some(String::from("x86_64-unknown-linux-gnu")),
+            false,
+            false,
+        )
+        .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(
+            picked,
+            (Some(String::from("x86_64-unknown-linux-gnu")), false)
+        );
+        assert_eq!(android_config(&config), defaults);
+
+        let picked = pick_target_triple(&mut config, None, true, false)
+            .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(
+            picked,
+            (Some(String::from("armv7-linux-androideabi")), true)
+        );
+    }
+
+    #[test]
+    fn android_builds_need_an_android_target() {
+        let target = || Some(String::from("x86_64-unknown-linux-gnu"));
+        let mut config = Config::from_toml(Path::new("/servo"), "");
+        let error = pick_target_triple(&mut config, target(), true, false).err();
+        assert_eq!(
+            error.map(|error| error.to_string()).as_deref(),
+            Some("x86_64-unknown-linux-gnu is not an android target")
+        );
+
+        let mut config = Config::from_toml(Path::new("/servo"), "[build]\nandroid = true");
+        assert!(pick_target_triple(&mut config, target(), false, false).is_err());
+    }
+
+    #[test]
+    fn cargo_features_are_merged_before_derived_features() {
+        let (features, _) = features_and_rustflags(
+            "",
+            no_flags(),
+            &[
+                "--release",
+                "--features",
+                "canvas2d-azure,layout-2020",
+                "-F",
+                "webgl-backtrace",
+            ],
+        );
+        assert_eq!(
+            features,
+            [
+                "canvas2d-azure",
+                "layout-2020",
+                "webgl-backtrace",
+                "native-bluetooth",
+                "egl"
+            ]
+        );
+
+        let (features, _) = features_and_rustflags(
+            "",
+            BuildFlags {
+                features: vec![String::from("media-gstreamer")],
+                ..no_flags()
+            },
+            &["--features=webdriver", "--", "--features", "x"],
+        );
+        assert_eq!(
+            features,
+            [
+                "media-gstreamer",
+                "webdriver",
+                "native-bluetooth",
+                "egl",
+                "canvas2d-raqote",
+                "layout-2013"
+            ]
+        );
+    }
+}
@@ -22,9 +22,11 @@ pub struct Config {
 pub struct ConfigTools {
     pub cache_dir: Option<PathBuf>,
     pub cargo_home_dir: Option<PathBuf>,
+    pub target_dir: Option<PathBuf>,
     pub use_rustup: Option<bool>,
     pub rustc_with_gold: Option<bool>,
     pub notify_command: Option<String>,
+    pub notify_threshold: Option<u64>,
 }
 
 #[derive(Default, Deserialize)]
@@ -53,7 +55,7 @@ pub struct ConfigAndroid {
 }
 
 impl Config {
-    pub fn from_str(s: &str) -> Self {
-        toml::from_str(s).unwrap_or(Default::default())
+    pub fn from_str(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
     }
 }
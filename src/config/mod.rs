@@ -1,3 +1,4 @@
+use crate::MarsError;
 use std::{
     env,
     fs::read_to_string,
@@ -16,11 +17,33 @@ pub struct Config {
 pub struct ConfigTools {
     pub cache_dir: PathBuf,
     pub cargo_home_dir: PathBuf,
+    pub target_dir: PathBuf,
     pub use_rustup: bool,
     pub rustc_with_gold: bool,
     pub notify_command: Option<String>,
+    pub notify_threshold: u64,
+    pub toolchain: Option<String>,
 }
 
+/// The `[build]` section of `.servobuild`.
+///
+/// Where a key has a matching command line flag, the two are combined
+/// as follows:
+///
+/// * `mode` is only used when neither `--dev` nor `--release` is passed.
+/// * `debug-mozjs` and `debug-assertions` are enabled if either the key
+///   or the `--debug-mozjs`/`--with-debug-assertions` flag is set.
+/// * `layout-2020` is overridden by either `--with-layout-2020` or
+///   `--with-layout-2013`, or by passing either layout's feature.
+/// * `rustflags` is appended to any `RUSTFLAGS` from the environment, so
+///   the config file wins where rustc takes the last of repeated flags.
+///   Flags from `--with-frame-pointer` and `--with-debug-assertions`
+///   are appended after it.
+/// * `incremental` sets `CARGO_INCREMENTAL`, overriding the environment.
+/// * `thinlto` sets `CARGO_PROFILE_RELEASE_LTO=thin`, so it only affects
+///   release builds.
+/// * `webgl-backtrace` and `dom-backtrace` enable the matching cargo
+///   features, and have no command line flag.
 pub struct ConfigBuild {
     pub mode: Option<String>,
     pub android: bool,
@@ -48,7 +71,7 @@ pub struct ConfigAndroid {
 }
 
 impl Config {
-    pub fn new(repo_root: &Path) -> Self {
+    pub fn new(repo_root: &Path) -> Result<Self, MarsError> {
         // In mach, this code is in command_base
         let config_path = {
             let mut config_path = repo_root.to_path_buf();
@@ -56,17 +79,55 @@ impl Config {
 
             config_path
         };
-        let config_file = match read_to_string(config_path) {
-            Ok(toml_str) => config_file::Config::from_str(&toml_str),
+        let config_file = match read_to_string(&config_path) {
+            Ok(toml_str) => config_file::Config::from_str(&toml_str).map_err(|error| {
+                MarsError::Config(format!(
+                    "Failed to parse {}: {}",
+                    config_path.display(),
+                    error
+                ))
+            })?,
             Err(_) => config_file::Config::default(),
         };
 
+        Ok(Self::resolve(repo_root, config_file, |name| {
+            env::var(name).ok()
+        }))
+    }
+
+    /// Parses `contents` as a `.servobuild` file, ignoring the environment.
+    #[cfg(test)]
+    pub fn from_toml(repo_root: &Path, contents: &str) -> Self {
+        Self::from_toml_and_env(repo_root, contents, &[])
+    }
+
+    /// Parses `contents` as a `.servobuild` file, with `env` in place
+    /// of the environment.
+    #[cfg(test)]
+    pub fn from_toml_and_env(repo_root: &Path, contents: &str, env: &[(&str, &str)]) -> Self {
+        let config_file =
+            config_file::Config::from_str(contents).expect("test config should be valid");
+
+        Self::resolve(repo_root, config_file, |name| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| String::from(*value))
+        })
+    }
+
+    /// Fills in the defaults for the keys missing from `config_file`,
+    /// reading environment variables through `env_var`.
+    fn resolve(
+        repo_root: &Path,
+        config_file: config_file::Config,
+        env_var: impl Fn(&str) -> Option<String>,
+    ) -> Self {
         // TODO tranlation
         // resolve_relative paths
         let cache_dir = config_file.tools.cache_dir.unwrap_or_else(|| {
-            env::var("SERVO_CACHE_DIR")
+            env_var("SERVO_CACHE_DIR")
                 .map(|path| PathBuf::from(path))
-                .unwrap_or_else(|_| {
+                .unwrap_or_else(|| {
                     let mut path = repo_root.to_path_buf();
                     path.push(".servo");
 
@@ -74,33 +135,43 @@ impl Config {
                 })
         });
         let cargo_home_dir = config_file.tools.cargo_home_dir.unwrap_or_else(|| {
-            env::var("SERVO_CACHE_DIR")
+            env_var("CARGO_HOME")
                 .map(|path| PathBuf::from(path))
-                .unwrap_or_else(|_| {
+                .unwrap_or_else(|| {
                     let mut path = repo_root.to_path_buf();
                     path.push(".cargo");
 
                     path
                 })
         });
+        let target_dir = target_dir(
+            repo_root,
+            &env::current_dir().unwrap_or_default(),
+            env_var("CARGO_TARGET_DIR"),
+            config_file.tools.target_dir,
+        );
         let use_rustup = config_file.tools.use_rustup.unwrap_or(true);
         let rustc_with_gold = config_file.tools.rustc_with_gold.unwrap_or_else(|| {
-            match env::var("SERVO_RUSTC_WITH_GOLD") {
-                Ok(val) => match val.as_str() {
+            match env_var("SERVO_RUSTC_WITH_GOLD") {
+                Some(val) => match val.as_str() {
                     "True" => true,
                     "False" => false,
                     _ => true,
                 },
-                Err(_) => true,
+                None => true,
             }
         });
 
         let config_tools = ConfigTools {
             cache_dir,
             cargo_home_dir,
+            target_dir,
             use_rustup,
             rustc_with_gold,
             notify_command: config_file.tools.notify_command,
+            notify_threshold: config_file.tools.notify_threshold.unwrap_or(30),
+            // The toolchain can only be overridden from the command line
+            toolchain: None,
         };
 
         let config_build = ConfigBuild {
@@ -136,3 +207,75 @@ impl Config {
         }
     }
 }
+
+/// Picks the target directory from `CARGO_TARGET_DIR`, given as
+/// `env_target_dir`, and the `target-dir` key of the config file.
+///
+/// CARGO_TARGET_DIR takes precedence over the config file, matching
+/// cargo itself. Relative paths are resolved against the current
+/// directory for the environment variable, as cargo does, and
+/// against the repo root for the config file.
+fn target_dir(
+    repo_root: &Path,
+    current_dir: &Path,
+    env_target_dir: Option<String>,
+    config_target_dir: Option<PathBuf>,
+) -> PathBuf {
+    env_target_dir
+        .map(|path| current_dir.join(path))
+        .or_else(|| config_target_dir.map(|path| repo_root.join(path)))
+        .unwrap_or_else(|| repo_root.join("target"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cargo_target_dir_overrides_the_config_file() {
+        let repo_root = Path::new("/servo");
+        let current_dir = Path::new("/servo/components/net");
+        let target_dir = |env: Option<&str>, config: Option<&str>| {
+            target_dir(
+                repo_root,
+                current_dir,
+                env.map(String::from),
+                config.map(PathBuf::from),
+            )
+        };
+
+        assert_eq!(target_dir(None, None), Path::new("/servo/target"));
+        assert_eq!(
+            target_dir(Some("build"), None),
+            Path::new("/servo/components/net/build")
+        );
+        assert_eq!(target_dir(None, Some("build")), Path::new("/servo/build"));
+        assert_eq!(
+            target_dir(Some("/tmp/env"), Some("/tmp/config")),
+            Path::new("/tmp/env")
+        );
+    }
+
+    #[test]
+    fn target_dir_from_the_environment_and_config_file() {
+        let repo_root = Path::new("/servo");
+        let config = "[tools]\ntarget-dir = \"/tmp/config\"\n";
+
+        assert_eq!(
+            Config::from_toml(repo_root, config).tools.target_dir,
+            Path::new("/tmp/config")
+        );
+        assert_eq!(
+            Config::from_toml_and_env(repo_root, "", &[("CARGO_TARGET_DIR", "/tmp/env")])
+                .tools
+                .target_dir,
+            Path::new("/tmp/env")
+        );
+        assert_eq!(
+            Config::from_toml_and_env(repo_root, config, &[("CARGO_TARGET_DIR", "/tmp/env")])
+                .tools
+                .target_dir,
+            Path::new("/tmp/env")
+        );
+    }
+}
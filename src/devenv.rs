@@ -0,0 +1,502 @@
+use crate::{
+    build::{format_duration, notify_build_done},
+    command_base::{
+        call_with_lines, cargo_build_like_command, prepare_cargo_build_like,
+        run_cargo_build_like_command,
+    },
+    CargoArgs, CheckArgs, ClippyArgs, CommonArgs, Config, MarsError,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// Run `cargo check` with the same target, features and environment
+/// as `build`, so the two share cargo's cache.
+pub fn check(
+    repo_root: PathBuf,
+    mut config: Config,
+    check_args: CheckArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let CheckArgs { no_clobber, params } = check_args;
+    let (flags, env) = prepare_cargo_build_like(&repo_root, &mut config, common_args, no_clobber)?;
+
+    let build_start = Instant::now();
+    let status =
+        run_cargo_build_like_command(&repo_root, &config, "check", params, env, false, flags);
+    let elapsed = build_start.elapsed();
+
+    notify_build_done(&config, elapsed, status.is_ok());
+
+    if status.is_ok() {
+        println!(
+            "Finished checking in {}, binary NOT updated. Consider mars build before running servo.",
+            format_duration(elapsed)
+        );
+    }
+
+    status
+}
+
+/// Run any cargo subcommand, such as `tree` or `fix`, with the same
+/// target, features and environment as `build`.
+pub fn cargo(
+    repo_root: PathBuf,
+    mut config: Config,
+    cargo_args: CargoArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let CargoArgs {
+        no_clobber,
+        subcommand,
+        params,
+    } = cargo_args;
+    let (flags, env) = prepare_cargo_build_like(&repo_root, &mut config, common_args, no_clobber)?;
+
+    run_cargo_build_like_command(&repo_root, &config, &subcommand, params, env, false, flags)
+}
+
+/// Run `cargo clippy` with the same target, features and environment as
+/// `build`, and compare the warnings found against a baseline.
+///
+/// The baseline lists the warnings which are already known about, so only
+/// new warnings fail the command. Warnings are matched by their lint, file
+/// and the code they point at, so moving code around doesn't make its
+/// warnings new.
+pub fn clippy(
+    repo_root: PathBuf,
+    mut config: Config,
+    clippy_args: ClippyArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let ClippyArgs {
+        no_clobber,
+        update_baseline,
+        baseline,
+        params,
+    } = clippy_args;
+    let baseline_path = baseline.unwrap_or_else(|| repo_root.join(CLIPPY_BASELINE));
+
+    let (flags, env) = prepare_cargo_build_like(&repo_root, &mut config, common_args, no_clobber)?;
+
+    let mut cargo_args = vec![String::from("--message-format=json")];
+    cargo_args.extend(params);
+    let (command, args, env) =
+        cargo_build_like_command(&repo_root, &config, "clippy", cargo_args, env, flags)?;
+
+    let build_start = Instant::now();
+    let mut warnings = ClippyWarnings::default();
+    let status = call_with_lines(command, args, env, false, |line| {
+        if let Some(output) = warnings.read_line(&repo_root, line) {
+            print!("{}", output);
+        }
+    });
+    notify_build_done(&config, build_start.elapsed(), status.is_ok());
+    status?;
+    let warnings = warnings.by_key;
+
+    let warning_count = warnings.values().map(Vec::len).sum::<usize>();
+    if update_baseline {
+        let entries = warnings
+            .iter()
+            .map(|(key, rendered)| BaselineEntry {
+                lint: key.lint.clone(),
+                file: key.file.clone(),
+                snippet: key.snippet.clone(),
+                count: rendered.len(),
+            })
+            .collect::<Vec<_>>();
+        let mut contents =
+            serde_json::to_string_pretty(&entries).expect("the baseline is always serializable");
+        contents.push('\n');
+        fs::write(&baseline_path, contents).map_err(|error| MarsError::Io {
+            context: format!("write {}", baseline_path.display()),
+            error,
+        })?;
+        println!(
+            "Wrote {} warnings to {}",
+            warning_count,
+            baseline_path.display()
+        );
+        return Ok(());
+    }
+
+    let baseline = read_baseline(&baseline_path)?;
+    let BaselineComparison { new, fixed_count } = compare_with_baseline(&warnings, &baseline);
+    for rendered in &new {
+        print!("{}", rendered);
+    }
+    let new_count = new.len();
+
+    println!(
+        "clippy found {} warnings, {} of which are not in the baseline.",
+        warning_count, new_count
+    );
+    if fixed_count > 0 {
+        println!(
+            "{} warnings in the baseline have been fixed, run mars clippy --update-baseline to remove them.",
+            fixed_count
+        );
+    }
+
+    if new_count == 0 {
+        Ok(())
+    } else {
+        Err(MarsError::CheckFailed(format!(
+            "clippy found {} new warnings.",
+            new_count
+        )))
+    }
+}
+
+/// The baseline used by `clippy`, relative to the repo root.
+const CLIPPY_BASELINE: &str = "clippy-baseline.json";
+
+/// What a warning is matched against the baseline on.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct WarningKey {
+    lint: String,
+    /// Relative to the repo root, using `/` as a separator.
+    file: String,
+    /// The code the warning points at, with whitespace collapsed.
+    snippet: String,
+}
+
+/// The warnings in the baseline with the same lint, file and snippet.
+#[derive(Serialize, Deserialize)]
+struct BaselineEntry {
+    lint: String,
+    file: String,
+    snippet: String,
+    count: usize,
+}
+
+/// The warnings in the output of `cargo clippy --message-format=json`.
+#[derive(Default)]
+struct ClippyWarnings {
+    /// The rendered message of each warning, by what it is matched on.
+    by_key: BTreeMap<WarningKey, Vec<String>>,
+    /// The package, target, lint and span of each warning read. Cargo
+    /// reports a warning once for each compilation unit it is built in,
+    /// such as a lib and its tests, and only the first is counted.
+    seen: HashSet<(String, String, String, SpanLocation)>,
+}
+
+impl ClippyWarnings {
+    /// Reads a line of cargo's output, returning what should be printed
+    /// for it. Warnings are only printed once compared to the baseline.
+    fn read_line(&mut self, repo_root: &Path, line: &str) -> Option<String> {
+        if !line.starts_with('{') {
+            return Some(format!("{}\n", line));
+        }
+        let (diagnostic, package_id, target) = match serde_json::from_str::<CargoMessage>(line) {
+            Ok(CargoMessage {
+                message: Some(diagnostic),
+                package_id,
+                target,
+            }) => (diagnostic, package_id, target),
+            _ => return None,
+        };
+        let rendered = diagnostic.rendered.clone().unwrap_or_default();
+        match diagnostic.level.as_str() {
+            "warning" => {
+                let key = diagnostic.key(repo_root)?;
+                let location = diagnostic.primary_span()?.location();
+                let target = target
+                    .map(|target| format!("{} {}", target.kind.join(","), target.name))
+                    .unwrap_or_default();
+                let unit = (
+                    package_id.unwrap_or_default(),
+                    target,
+                    key.lint.clone(),
+                    location,
+                );
+                if self.seen.insert(unit) {
+                    self.by_key.entry(key).or_default().push(rendered);
+                }
+                None
+            }
+            "error" => Some(rendered),
+            _ => None,
+        }
+    }
+}
+
+/// The warnings clippy found which aren't in the baseline, and how many
+/// warnings in the baseline it no longer finds.
+struct BaselineComparison<'a> {
+    /// The rendered message of each new warning.
+    new: Vec<&'a str>,
+    fixed_count: usize,
+}
+
+/// Compares the rendered messages of the `warnings` clippy found with the
+/// number of each warning in the `baseline`.
+fn compare_with_baseline<'a>(
+    warnings: &'a BTreeMap<WarningKey, Vec<String>>,
+    baseline: &BTreeMap<WarningKey, usize>,
+) -> BaselineComparison<'a> {
+    let new = warnings
+        .iter()
+        .flat_map(|(key, rendered)| {
+            let known = baseline.get(key).copied().unwrap_or(0);
+            rendered.iter().skip(known).map(String::as_str)
+        })
+        .collect();
+    let fixed_count = baseline
+        .iter()
+        .map(|(key, &count)| count.saturating_sub(warnings.get(key).map_or(0, Vec::len)))
+        .sum();
+
+    BaselineComparison { new, fixed_count }
+}
+
+/// A line of the output of `cargo --message-format=json`.
+#[derive(Deserialize)]
+struct CargoMessage {
+    /// Only set for the compiler's diagnostics.
+    message: Option<Diagnostic>,
+    package_id: Option<String>,
+    target: Option<CargoTarget>,
+}
+
+/// The target of a package a message is about, such as its lib.
+#[derive(Deserialize)]
+struct CargoTarget {
+    kind: Vec<String>,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    level: String,
+    code: Option<DiagnosticCode>,
+    spans: Vec<DiagnosticSpan>,
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticSpan {
+    file_name: String,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+    text: Vec<DiagnosticSpanLine>,
+}
+
+/// The file, and the lines and columns, a span covers.
+type SpanLocation = (String, usize, usize, usize, usize);
+
+impl DiagnosticSpan {
+    fn location(&self) -> SpanLocation {
+        (
+            self.file_name.clone(),
+            self.line_start,
+            self.column_start,
+            self.line_end,
+            self.column_end,
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct DiagnosticSpanLine {
+    text: String,
+    /// The 1 based column the span starts on, in characters.
+    highlight_start: usize,
+    highlight_end: usize,
+}
+
+impl Diagnostic {
+    fn primary_span(&self) -> Option<&DiagnosticSpan> {
+        self.spans.iter().find(|span| span.is_primary)
+    }
+
+    /// Returns what this diagnostic is matched against the baseline on,
+    /// or `None` for diagnostics without a location, such as the count of
+    /// warnings printed at the end.
+    fn key(&self, repo_root: &Path) -> Option<WarningKey> {
+        let span = self.primary_span()?;
+        let file = Path::new(&span.file_name);
+        let file = file.strip_prefix(repo_root).unwrap_or(file);
+        let snippet = span
+            .text
+            .iter()
+            .map(|line| {
+                line.text
+                    .chars()
+                    .skip(line.highlight_start.saturating_sub(1))
+                    .take(line.highlight_end.saturating_sub(line.highlight_start))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Some(WarningKey {
+            lint: self
+                .code
+                .as_ref()
+                .map_or_else(|| String::from("warning"), |code| code.code.clone()),
+            file: file.to_string_lossy().replace('\\', "/"),
+            snippet: snippet.split_whitespace().collect::<Vec<_>>().join(" "),
+        })
+    }
+}
+
+/// Returns the number of warnings in the baseline at `path` for each
+/// lint, file and snippet. A missing baseline is treated as empty.
+fn read_baseline(path: &Path) -> Result<BTreeMap<WarningKey, usize>, MarsError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => {
+            println!(
+                "No clippy baseline found at {}, run mars clippy --update-baseline to create one.",
+                path.display()
+            );
+            return Ok(BTreeMap::new());
+        }
+    };
+    parse_baseline(&contents).map_err(|error| {
+        MarsError::Config(format!("Failed to parse {}: {}", path.display(), error))
+    })
+}
+
+/// Parses the contents of a baseline, summing the counts of any entries
+/// for the same warning.
+fn parse_baseline(contents: &str) -> Result<BTreeMap<WarningKey, usize>, serde_json::Error> {
+    let entries: Vec<BaselineEntry> = serde_json::from_str(contents)?;
+
+    let mut baseline = BTreeMap::new();
+    for entry in entries {
+        let key = WarningKey {
+            lint: entry.lint,
+            file: entry.file,
+            snippet: entry.snippet,
+        };
+        *baseline.entry(key).or_default() += entry.count;
+    }
+
+    Ok(baseline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A warning from `cargo clippy --message-format=json`, trimmed of the
+    /// fields mars doesn't read.
+    const WARNING: &str = r#"{"reason":"compiler-message","package_id":"path+file:///servo/components/script#0.0.1","target":{"kind":["lib"],"crate_types":["rlib"],"name":"script","src_path":"/servo/components/script/lib.rs","edition":"2018"},"message":{"rendered":"warning: unused variable: `x`\n","children":[],"level":"warning","message":"unused variable: `x`","spans":[{"column_end":20,"column_start":9,"file_name":"/servo/components/script/dom/node.rs","is_primary":true,"line_end":3,"line_start":2,"text":[{"highlight_end":15,"highlight_start":9,"text":"    let (x,   "},{"highlight_end":20,"highlight_start":1,"text":"         y) = pair;"}]}],"code":{"code":"unused_variables","explanation":null}}}"#;
+
+    fn key(lint: &str, file: &str, snippet: &str) -> WarningKey {
+        WarningKey {
+            lint: String::from(lint),
+            file: String::from(file),
+            snippet: String::from(snippet),
+        }
+    }
+
+    #[test]
+    fn warning_keys() {
+        let message: CargoMessage =
+            serde_json::from_str(WARNING).unwrap_or_else(|error| panic!("{}", error));
+        let diagnostic = message.message.expect("the warning has a message");
+        assert_eq!(
+            diagnostic.key(Path::new("/servo")),
+            Some(key(
+                "unused_variables",
+                "components/script/dom/node.rs",
+                "(x, y) = pair;"
+            ))
+        );
+
+        let windows = WARNING
+            .replace(
+                "/servo/components/script/dom/node.rs",
+                r"components\\script\\dom\\node.rs",
+            )
+            .replace(
+                r#","code":{"code":"unused_variables","explanation":null}"#,
+                r#","code":null"#,
+            );
+        let message: CargoMessage =
+            serde_json::from_str(&windows).unwrap_or_else(|error| panic!("{}", error));
+        let diagnostic = message.message.expect("the warning has a message");
+        assert_eq!(
+            diagnostic.key(Path::new("/servo")),
+            Some(key(
+                "warning",
+                "components/script/dom/node.rs",
+                "(x, y) = pair;"
+            ))
+        );
+    }
+
+    #[test]
+    fn warnings_are_counted_once_per_target() {
+        let repo_root = Path::new("/servo");
+        let mut warnings = ClippyWarnings::default();
+        // Once for the lib, once for its tests, and once for a lib with the same name in
+        // another package, which has a different package id
+        let other_package = WARNING.replace("components/script#", "components/other#");
+        for line in &[WARNING, WARNING, other_package.as_str()] {
+            assert_eq!(warnings.read_line(repo_root, line), None);
+        }
+        let moved = WARNING.replace(r#""line_start":2"#, r#""line_start":1"#);
+        assert_eq!(warnings.read_line(repo_root, &moved), None);
+        assert_eq!(
+            warnings
+                .read_line(repo_root, "   Compiling script v0.0.1")
+                .as_deref(),
+            Some("   Compiling script v0.0.1\n")
+        );
+        let error = WARNING.replace(r#""level":"warning""#, r#""level":"error""#);
+        assert_eq!(
+            warnings.read_line(repo_root, &error).as_deref(),
+            Some("warning: unused variable: `x`\n")
+        );
+
+        let counts = warnings.by_key.values().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(counts, [3]);
+    }
+
+    #[test]
+    fn baseline_comparison() {
+        let warnings: BTreeMap<WarningKey, Vec<String>> = vec![
+            (
+                key("a", "a.rs", "x"),
+                vec![String::from("a 1"), String::from("a 2")],
+            ),
+            (key("b", "b.rs", "y"), vec![String::from("b 1")]),
+        ]
+        .into_iter()
+        .collect();
+        let baseline = parse_baseline(
+            r#"[
+                {"lint": "a", "file": "a.rs", "snippet": "x", "count": 1},
+                {"lint": "c", "file": "c.rs", "snippet": "z", "count": 2},
+                {"lint": "c", "file": "c.rs", "snippet": "z", "count": 1}
+            ]"#,
+        )
+        .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(baseline.get(&key("c", "c.rs", "z")), Some(&3));
+
+        let comparison = compare_with_baseline(&warnings, &baseline);
+        assert_eq!(comparison.new, ["a 2", "b 1"]);
+        assert_eq!(comparison.fixed_count, 3);
+
+        let comparison = compare_with_baseline(&warnings, &BTreeMap::new());
+        assert_eq!(comparison.new, ["a 1", "a 2", "b 1"]);
+        assert_eq!(comparison.fixed_count, 0);
+    }
+}
@@ -0,0 +1,68 @@
+use std::{fmt, io};
+
+/// The ways in which a mars command can fail.
+///
+/// Each variant maps to a process exit code, see `exit_code`.
+pub enum MarsError {
+    /// Mars was not run from within a servo repository.
+    RepoNotFound,
+    /// The `.servobuild` file or the command line arguments are invalid.
+    Config(String),
+    /// The rust toolchain, or another dependency installed by
+    /// bootstrapping, is missing.
+    MissingToolchain(String),
+    /// An IO operation, such as spawning a child process, failed.
+    Io { context: String, error: io::Error },
+    /// A child process ran but exited unsuccessfully. The exit code
+    /// is `None` if the child was terminated by a signal.
+    ChildFailed { command: String, code: Option<i32> },
+    /// The servo binary or rr trace a command needs does not exist.
+    BuildNotFound(String),
+    /// A check such as `test-tidy` ran and found problems.
+    CheckFailed(String),
+}
+
+impl MarsError {
+    /// The exit code mars should return for this error.
+    ///
+    /// * `ChildFailed` - the exit code of the child process, or 1 if
+    ///   the child was terminated by a signal
+    /// * `Config` - 2
+    /// * `RepoNotFound` - 3
+    /// * `MissingToolchain` - 4
+    /// * `Io` - 5
+    /// * `BuildNotFound` - 6
+    /// * `CheckFailed` - 7
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MarsError::ChildFailed { code, .. } => code.unwrap_or(1),
+            MarsError::Config(_) => 2,
+            MarsError::RepoNotFound => 3,
+            MarsError::MissingToolchain(_) => 4,
+            MarsError::Io { .. } => 5,
+            MarsError::BuildNotFound(_) => 6,
+            MarsError::CheckFailed(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for MarsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarsError::RepoNotFound => write!(f, "You must run mars within a servo repository."),
+            MarsError::Config(message) => write!(f, "{}", message),
+            MarsError::MissingToolchain(message) => write!(f, "{}", message),
+            MarsError::BuildNotFound(message) => write!(f, "{}", message),
+            MarsError::CheckFailed(message) => write!(f, "{}", message),
+            MarsError::Io { context, error } => write!(f, "Failed to {}: {}", context, error),
+            MarsError::ChildFailed {
+                command,
+                code: Some(code),
+            } => write!(f, "`{}` exited with status {}", command, code),
+            MarsError::ChildFailed {
+                command,
+                code: None,
+            } => write!(f, "`{}` was terminated by a signal", command),
+        }
+    }
+}
@@ -6,49 +6,93 @@ use std::{
 use structopt::StructOpt;
 
 mod args;
-pub use args::{Args, BuildArgs, CommonArgs, Subcommands};
+pub use args::{
+    Args, BuildArgs, CargoArgs, CheckArgs, CleanArgs, ClippyArgs, CommonArgs, DocArgs, FmtArgs,
+    ReportFormat, RrPruneArgs, RrRecordArgs, RrReplayArgs, RunArgs, Subcommands, TestTidyArgs,
+    TestUnitArgs,
+};
 
 mod build;
-use build::build;
+use build::{build, clean};
+
+mod command_base;
+
+mod devenv;
+use devenv::{cargo, check, clippy};
+
+mod post_build;
+use post_build::{doc, rr_list, rr_prune, rr_record, rr_replay, run};
+
+mod testing;
+use testing::{fmt, test_tidy, test_unit};
+
+mod tidy;
 
 mod config;
 pub use config::Config;
 
+mod error;
+pub use error::MarsError;
+
 fn main() {
     let args = Args::from_args();
 
-    let repo_root = match get_repo_root() {
-        Some(path) => path,
-        None => {
-            eprintln!("You must run mars within a servo repository.");
-            exit(1)
-        }
-    };
+    if let Err(error) = try_main(args) {
+        eprintln!("{}", error);
+        exit(error.exit_code());
+    }
+}
 
-    let config = Config::new(&repo_root);
+fn try_main(args: Args) -> Result<(), MarsError> {
+    let repo_root = get_repo_root()?.ok_or(MarsError::RepoNotFound)?;
+
+    let mut config = Config::new(&repo_root)?;
+    if let Some(toolchain) = &args.common.toolchain {
+        config.tools.toolchain = Some(toolchain.clone());
+    }
 
     match args.cmd {
         Subcommands::Build(build_args) => build(repo_root, config, build_args, args.common),
-    };
+        Subcommands::Cargo(cargo_args) => cargo(repo_root, config, cargo_args, args.common),
+        Subcommands::Check(check_args) => check(repo_root, config, check_args, args.common),
+        Subcommands::Clean(clean_args) => clean(repo_root, config, clean_args, args.common),
+        Subcommands::Clippy(clippy_args) => clippy(repo_root, config, clippy_args, args.common),
+        Subcommands::Doc(doc_args) => doc(repo_root, config, doc_args, args.common),
+        Subcommands::Fmt(fmt_args) => fmt(repo_root, config, fmt_args),
+        Subcommands::Run(run_args) => run(repo_root, config, run_args, args.common),
+        Subcommands::RrRecord(rr_record_args) => {
+            rr_record(repo_root, config, rr_record_args, args.common)
+        }
+        Subcommands::RrReplay(rr_replay_args) => rr_replay(config, rr_replay_args),
+        Subcommands::RrList => rr_list(config),
+        Subcommands::RrPrune(rr_prune_args) => rr_prune(config, rr_prune_args),
+        Subcommands::TestTidy(test_tidy_args) => test_tidy(repo_root, test_tidy_args),
+        Subcommands::TestUnit(test_unit_args) => {
+            test_unit(repo_root, config, test_unit_args, args.common)
+        }
+    }
 }
 
 /// When the current working directory is either the root
 /// of a servo repository or a subdirectory of a servo
 /// repository, this will return the path to the root of
 /// the repository. Otherwise it will return None.
-fn get_repo_root() -> Option<PathBuf> {
-    let mut current_dir = env::current_dir().expect("failed to read current working directory");
+fn get_repo_root() -> Result<Option<PathBuf>, MarsError> {
+    let mut current_dir = env::current_dir().map_err(|error| MarsError::Io {
+        context: String::from("read current working directory"),
+        error,
+    })?;
 
     loop {
         if is_servo_repo_root(&current_dir) {
-            return Some(current_dir);
+            return Ok(Some(current_dir));
         }
 
         // mutate current_dir into it's parent directory
         // and return None if there is no parent (we've
         // recursed all the way to root)
         if !current_dir.pop() {
-            return None;
+            return Ok(None);
         }
     }
 }
@@ -0,0 +1,649 @@
+use crate::{
+    build::format_duration,
+    command_base::{
+        build_env, call, dir_size, format_size, get_binary_path, get_target_dir, is_on_path,
+        path_to_string, pick_target_triple, prepare_cargo_build_like, run_cargo_build_like_command,
+        rust_toolchain, rustup_output,
+    },
+    CommonArgs, Config, DocArgs, MarsError, RrPruneArgs, RrRecordArgs, RrReplayArgs, RunArgs,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::SystemTime,
+};
+
+/// The debuggers to look for on the PATH, in order, when `--debugger`
+/// is passed without naming one.
+#[cfg(target_os = "macos")]
+const DEFAULT_DEBUGGERS: &[&str] = &["lldb", "gdb"];
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_DEBUGGERS: &[&str] = &["gdb", "cgdb", "lldb"];
+
+/// Run Servo
+pub fn run(
+    repo_root: PathBuf,
+    mut config: Config,
+    run_args: RunArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let RunArgs {
+        release,
+        dev,
+        bin,
+        debugger,
+        debugger_cmd,
+        params,
+    } = run_args;
+    let CommonArgs {
+        target,
+        android,
+        magicleap,
+        libsimpleservo,
+        ..
+    } = common_args;
+
+    let (target, android) = pick_target_triple(&mut config, target, android, magicleap)?;
+
+    if android {
+        // TODO translation
+        // mach installs and launches the apk using adb
+        return Err(MarsError::Config(String::from(
+            "Running Servo on android is not supported yet.",
+        )));
+    }
+
+    let mut env = build_env(&repo_root, &config, &target, false, false, &[])?;
+    env.insert(String::from("RUST_BACKTRACE"), String::from("1"));
+
+    let binary = servo_binary(
+        &config,
+        release,
+        dev,
+        bin,
+        &target,
+        android,
+        magicleap,
+        libsimpleservo,
+    )?;
+
+    // TODO translation
+    // mach also supports --headless, --software and --nightly here
+
+    let mut args = vec![path_to_string(&binary)];
+    args.extend(params);
+
+    // Prepend the debugger and its arguments
+    let debugger_command = match (debugger_cmd, debugger) {
+        (Some(debugger_cmd), _) => debugger_cmd.split_whitespace().map(String::from).collect(),
+        (None, Some(debugger)) => debugger_command(debugger, &env)?,
+        (None, None) => vec![],
+    };
+    let mut command = debugger_command.into_iter().chain(args);
+
+    call(
+        command.next().expect("command always contains the binary"),
+        command.collect(),
+        env,
+        false,
+    )
+}
+
+/// Returns `bin` if it was passed, otherwise the path of the release
+/// or dev build of servo.
+#[allow(clippy::too_many_arguments)]
+fn servo_binary(
+    config: &Config,
+    release: bool,
+    dev: bool,
+    bin: Option<PathBuf>,
+    target: &Option<String>,
+    android: bool,
+    magicleap: bool,
+    simpleservo: bool,
+) -> Result<PathBuf, MarsError> {
+    match bin {
+        Some(bin) => {
+            if !bin.exists() {
+                return Err(MarsError::BuildNotFound(format!(
+                    "Servo binary {} can't be found!",
+                    bin.display()
+                )));
+            }
+
+            Ok(bin)
+        }
+        None => get_binary_path(
+            config,
+            release,
+            dev,
+            target,
+            android,
+            magicleap,
+            simpleservo,
+        ),
+    }
+}
+
+/// Run Servo whilst recording execution with rr
+///
+/// Traces are written to the `rr` directory inside the servo cache
+/// directory, rather than rr's default of `~/.local/share/rr`.
+pub fn rr_record(
+    repo_root: PathBuf,
+    mut config: Config,
+    rr_record_args: RrRecordArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let RrRecordArgs {
+        release,
+        dev,
+        bin,
+        chaos,
+        params,
+    } = rr_record_args;
+    let CommonArgs {
+        target,
+        android,
+        magicleap,
+        libsimpleservo,
+        ..
+    } = common_args;
+
+    let (target, android) = pick_target_triple(&mut config, target, android, magicleap)?;
+
+    let mut env = build_env(&repo_root, &config, &target, false, false, &[])?;
+    env.insert(String::from("RUST_BACKTRACE"), String::from("1"));
+
+    let trace_dir = rr_trace_dir(&config);
+    fs::create_dir_all(&trace_dir).map_err(|error| MarsError::Io {
+        context: format!("create rr trace directory {}", trace_dir.display()),
+        error,
+    })?;
+    env.insert(String::from("_RR_TRACE_DIR"), path_to_string(&trace_dir));
+
+    let binary = servo_binary(
+        &config,
+        release,
+        dev,
+        bin,
+        &target,
+        android,
+        magicleap,
+        libsimpleservo,
+    )?;
+
+    call(
+        String::from("rr"),
+        rr_record_command(&binary, chaos, params),
+        env,
+        false,
+    )
+}
+
+/// Returns the arguments for rr to record `binary` running with `params`.
+fn rr_record_command(binary: &Path, chaos: bool, params: Vec<String>) -> Vec<String> {
+    let mut rr_args = vec![String::from("--fatal-errors"), String::from("record")];
+    if chaos {
+        rr_args.push(String::from("--chaos"));
+    }
+    rr_args.push(path_to_string(binary));
+    rr_args.extend(params);
+
+    rr_args
+}
+
+/// Replay an execution of Servo that was recorded with rr
+///
+/// `trace` may be the name of a trace in the trace directory, or a path
+/// to a trace. When it is not given the most recent trace is replayed.
+pub fn rr_replay(config: Config, rr_replay_args: RrReplayArgs) -> Result<(), MarsError> {
+    let RrReplayArgs { trace } = rr_replay_args;
+
+    let trace_dir = rr_trace_dir(&config);
+    let rr_args = rr_replay_command(&trace_dir, trace, |path| path.exists())?;
+
+    let mut env = HashMap::new();
+    env.insert(String::from("_RR_TRACE_DIR"), path_to_string(&trace_dir));
+
+    call(String::from("rr"), rr_args, env, false)
+}
+
+/// Returns the arguments for rr to replay `trace`, or the latest trace in
+/// `trace_dir` if it is not given. `exists` is used to check for traces.
+fn rr_replay_command(
+    trace_dir: &Path,
+    trace: Option<String>,
+    exists: impl Fn(&Path) -> bool,
+) -> Result<Vec<String>, MarsError> {
+    let trace = match trace {
+        Some(trace) => {
+            let named_trace = trace_dir.join(&trace);
+            if exists(&named_trace) {
+                named_trace
+            } else {
+                PathBuf::from(trace)
+            }
+        }
+        None => trace_dir.join("latest-trace"),
+    };
+    if !exists(&trace) {
+        return Err(MarsError::BuildNotFound(format!(
+            "No rr trace found at {}. Record one with `mars rr-record`.",
+            trace.display()
+        )));
+    }
+
+    Ok(vec![
+        String::from("--fatal-errors"),
+        String::from("replay"),
+        path_to_string(&trace),
+    ])
+}
+
+/// List the executions of Servo recorded with rr, newest first
+pub fn rr_list(config: Config) -> Result<(), MarsError> {
+    let trace_dir = rr_trace_dir(&config);
+    let traces = rr_traces(&trace_dir)?;
+    if traces.is_empty() {
+        println!("No rr traces in {}", trace_dir.display());
+        return Ok(());
+    }
+
+    println!("rr traces in {}:", trace_dir.display());
+    for (index, (trace, modified)) in traces.iter().enumerate() {
+        let age = modified.elapsed().unwrap_or_default();
+        println!(
+            "    {:<16} {:>10}  recorded {} ago{}",
+            trace
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            format_size(dir_size(trace)),
+            format_duration(age),
+            if index == 0 { " (latest)" } else { "" }
+        );
+    }
+
+    Ok(())
+}
+
+/// Delete all but the newest `keep` executions of Servo recorded with rr
+pub fn rr_prune(config: Config, rr_prune_args: RrPruneArgs) -> Result<(), MarsError> {
+    let RrPruneArgs { keep } = rr_prune_args;
+
+    let trace_dir = rr_trace_dir(&config);
+    let traces = rr_traces(&trace_dir)?;
+    let mut freed = 0;
+    for (trace, _) in traces.iter().skip(keep) {
+        freed += dir_size(trace);
+        println!("Removing {}", trace.display());
+        fs::remove_dir_all(trace).map_err(|error| MarsError::Io {
+            context: format!("remove rr trace {}", trace.display()),
+            error,
+        })?;
+    }
+
+    // rr leaves a dangling latest-trace link if every trace was removed
+    let latest_trace = trace_dir.join("latest-trace");
+    if fs::symlink_metadata(&latest_trace).is_ok() && !latest_trace.exists() {
+        let _ = fs::remove_file(&latest_trace);
+    }
+
+    println!("Freed {}", format_size(freed));
+
+    Ok(())
+}
+
+fn rr_trace_dir(config: &Config) -> PathBuf {
+    let mut trace_dir = config.tools.cache_dir.clone();
+    trace_dir.push("rr");
+
+    trace_dir
+}
+
+/// Returns the traces in `trace_dir` along with the time they
+/// were last modified, newest first.
+fn rr_traces(trace_dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>, MarsError> {
+    let entries = match fs::read_dir(trace_dir) {
+        Ok(entries) => entries,
+        // Nothing has been recorded yet
+        Err(_) => return Ok(vec![]),
+    };
+
+    let mut traces = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|error| MarsError::Io {
+            context: format!("read rr trace directory {}", trace_dir.display()),
+            error,
+        })?;
+        // Skip the latest-trace symlink, which points at one of the traces
+        let metadata = match fs::symlink_metadata(entry.path()) {
+            Ok(metadata) if metadata.is_dir() => metadata,
+            _ => continue,
+        };
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+        traces.push((entry.path(), modified));
+    }
+    traces.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    Ok(traces)
+}
+
+/// Generate documentation
+///
+/// The docs are built with the same features as `build`, so items behind
+/// `cfg`s that `build` enables are documented.
+pub fn doc(
+    repo_root: PathBuf,
+    mut config: Config,
+    doc_args: DocArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let DocArgs {
+        no_clobber,
+        open,
+        document_private_items,
+        package,
+        params,
+    } = doc_args;
+    let (flags, env) = prepare_cargo_build_like(&repo_root, &mut config, common_args, no_clobber)?;
+
+    let mut docs = get_target_dir(&config);
+    docs.push("doc");
+    fs::create_dir_all(&docs).map_err(|error| MarsError::Io {
+        context: format!("create {}", docs.display()),
+        error,
+    })?;
+
+    copy_rust_docs(&repo_root, &config, &docs)?;
+
+    let mut opts = vec![];
+    if document_private_items {
+        opts.push(String::from("--document-private-items"));
+    }
+    for package in &package {
+        opts.push(String::from("-p"));
+        opts.push(package.clone());
+    }
+    opts.extend(params);
+
+    run_cargo_build_like_command(&repo_root, &config, "doc", opts, env, false, flags)?;
+
+    let mut static_files = repo_root.clone();
+    static_files.push("etc");
+    static_files.push("doc.servo.org");
+    if let Ok(entries) = fs::read_dir(&static_files) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let destination = docs.join(entry.file_name());
+            fs::copy(entry.path(), &destination).map_err(|error| MarsError::Io {
+                context: format!(
+                    "copy {} to {}",
+                    entry.path().display(),
+                    destination.display()
+                ),
+                error,
+            })?;
+        }
+    }
+
+    if open {
+        // Open the docs for the first package asked for, or servo's
+        let krate = package
+            .first()
+            .map(|package| package.replace('-', "_"))
+            .unwrap_or_else(|| String::from("servo"));
+        let mut index = docs;
+        index.push(krate);
+        index.push("index.html");
+        open_in_browser(&index)?;
+    }
+
+    Ok(())
+}
+
+/// Copy the standard library docs from the toolchain into `docs`, so
+/// links from Servo's docs to std work offline.
+fn copy_rust_docs(repo_root: &Path, config: &Config, docs: &Path) -> Result<(), MarsError> {
+    // TODO translation
+    // mach only supports finding the toolchain through rustup
+    if !config.tools.use_rustup {
+        return Ok(());
+    }
+
+    let toolchain = rust_toolchain(repo_root, config)?;
+    let rustc_path = rustup_output(&["which", "--toolchain", &toolchain, "rustc"])?;
+    let rust_docs = Path::new(rustc_path.trim())
+        .parent()
+        .and_then(Path::parent)
+        .map(|toolchain_path| toolchain_path.join("share/doc/rust/html"))
+        .filter(|rust_docs| rust_docs.exists());
+    let rust_docs = match rust_docs {
+        Some(rust_docs) => rust_docs,
+        None => {
+            eprintln!(
+                "[Warning] The Rust documentation is not installed, run `rustup component add rust-docs --toolchain {}` to include it.",
+                toolchain
+            );
+            return Ok(());
+        }
+    };
+
+    let version_info = |dir: &Path| fs::read(dir.join("version_info.html")).ok();
+    if version_info(docs) == version_info(&rust_docs) {
+        return Ok(());
+    }
+
+    println!("Copying Rust documentation.");
+    copy_dir(&rust_docs, docs)
+}
+
+/// Copy the contents of `source` into `destination`, replacing
+/// anything with the same name. Hidden files are skipped.
+fn copy_dir(source: &Path, destination: &Path) -> Result<(), MarsError> {
+    let io_error = |path: &Path| {
+        let context = format!("copy {}", path.display());
+        move |error| MarsError::Io { context, error }
+    };
+
+    fs::create_dir_all(destination).map_err(io_error(destination))?;
+    for entry in fs::read_dir(source).map_err(io_error(source))? {
+        let entry = entry.map_err(io_error(source))?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let destination = destination.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &destination)?;
+        } else {
+            fs::copy(entry.path(), &destination).map_err(io_error(&entry.path()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+const OPEN_COMMAND: &[&str] = &["open"];
+#[cfg(windows)]
+const OPEN_COMMAND: &[&str] = &["cmd", "/c", "start", ""];
+#[cfg(not(any(target_os = "macos", windows)))]
+const OPEN_COMMAND: &[&str] = &["xdg-open"];
+
+fn open_in_browser(path: &Path) -> Result<(), MarsError> {
+    if !path.exists() {
+        return Err(MarsError::BuildNotFound(format!(
+            "No documentation found at {}.",
+            path.display()
+        )));
+    }
+
+    println!("Opening {}", path.display());
+    let mut args = OPEN_COMMAND[1..]
+        .iter()
+        .map(|arg| String::from(*arg))
+        .collect::<Vec<_>>();
+    args.push(path_to_string(path));
+
+    call(String::from(OPEN_COMMAND[0]), args, HashMap::new(), false)
+}
+
+/// Returns the debugger to run along with the arguments it needs before
+/// the servo binary, picking a default debugger if none was named.
+///
+/// When gdb or lldb is used, the rust-gdb or rust-lldb wrapper is
+/// preferred if it is installed.
+fn debugger_command(
+    debugger: Option<String>,
+    env: &HashMap<String, String>,
+) -> Result<Vec<String>, MarsError> {
+    let debugger = match debugger {
+        Some(debugger) => debugger,
+        None => DEFAULT_DEBUGGERS
+            .iter()
+            .find(|debugger| is_on_path(debugger))
+            .map(|debugger| String::from(*debugger))
+            .ok_or_else(|| {
+                MarsError::Config(String::from(
+                    "Could not find a suitable debugger in your PATH.",
+                ))
+            })?,
+    };
+
+    let debugger_args: &[&str] = match debugger.as_str() {
+        "gdb" | "cgdb" | "rust-gdb" => &["-q", "--args"],
+        "lldb" | "rust-lldb" => &["--"],
+        _ => {
+            return Err(MarsError::Config(format!(
+                "Unknown debugger `{}`, use --debugger-cmd to run a custom debugger.",
+                debugger
+            )))
+        }
+    };
+    if !is_on_path(&debugger) {
+        return Err(MarsError::Config(format!(
+            "Could not find {} in your PATH.",
+            debugger
+        )));
+    }
+
+    let mut command = debugger;
+    if command == "gdb" || command == "lldb" {
+        let rust_command = format!("rust-{}", command);
+        let has_rust_command = Command::new(&rust_command)
+            .arg("--version")
+            .envs(env)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        if has_rust_command {
+            command = rust_command;
+        }
+    }
+
+    let mut debugger_command = vec![command];
+    debugger_command.extend(debugger_args.iter().map(|arg| String::from(*arg)));
+
+    Ok(debugger_command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn rr_traces_are_kept_in_the_cache_dir() {
+        let config = Config::from_toml(Path::new("/servo"), "");
+        assert_eq!(rr_trace_dir(&config), Path::new("/servo/.servo/rr"));
+
+        let config = Config::from_toml(Path::new("/servo"), "[tools]\ncache-dir = \"/cache\"\n");
+        assert_eq!(rr_trace_dir(&config), Path::new("/cache/rr"));
+    }
+
+    #[test]
+    fn rr_record_runs_servo_with_its_arguments() {
+        let binary = Path::new("/servo/target/debug/servo");
+        assert_eq!(
+            rr_record_command(
+                binary,
+                false,
+                strings(&["-z", "tests/html/about-mozilla.html"])
+            ),
+            strings(&[
+                "--fatal-errors",
+                "record",
+                "/servo/target/debug/servo",
+                "-z",
+                "tests/html/about-mozilla.html"
+            ])
+        );
+        assert_eq!(
+            rr_record_command(binary, true, vec![]),
+            strings(&[
+                "--fatal-errors",
+                "record",
+                "--chaos",
+                "/servo/target/debug/servo"
+            ])
+        );
+    }
+
+    #[test]
+    fn rr_replay_finds_the_trace() {
+        let trace_dir = Path::new("/cache/rr");
+        let traces = [
+            Path::new("/cache/rr/latest-trace"),
+            Path::new("/cache/rr/servo-0"),
+            Path::new("/tmp/servo-1"),
+        ];
+        let exists = |path: &Path| traces.contains(&path);
+        let replay = |trace: Option<&str>| {
+            rr_replay_command(trace_dir, trace.map(String::from), exists)
+                .unwrap_or_else(|error| panic!("{}", error))
+        };
+
+        assert_eq!(
+            replay(None),
+            strings(&["--fatal-errors", "replay", "/cache/rr/latest-trace"])
+        );
+        assert_eq!(
+            replay(Some("servo-0")),
+            strings(&["--fatal-errors", "replay", "/cache/rr/servo-0"])
+        );
+        assert_eq!(
+            replay(Some("/tmp/servo-1")),
+            strings(&["--fatal-errors", "replay", "/tmp/servo-1"])
+        );
+    }
+
+    #[test]
+    fn rr_replay_without_a_trace_is_an_error() {
+        let trace_dir = Path::new("/cache/rr");
+        let error = |trace: Option<&str>| match rr_replay_command(
+            trace_dir,
+            trace.map(String::from),
+            |_| false,
+        ) {
+            Ok(_) => panic!("a missing trace should be an error"),
+            Err(error) => error.to_string(),
+        };
+
+        assert_eq!(
+            error(None),
+            "No rr trace found at /cache/rr/latest-trace. Record one with `mars rr-record`."
+        );
+        assert_eq!(
+            error(Some("servo-2")),
+            "No rr trace found at servo-2. Record one with `mars rr-record`."
+        );
+    }
+}
@@ -0,0 +1,692 @@
+use crate::{
+    command_base::{
+        build_env, call_rustup_run, call_with_lines, default_git_base, ensure_bootstrapped,
+        get_target_dir, host_triple, path_to_string, rustup_run_command,
+    },
+    tidy, CommonArgs, Config, FmtArgs, MarsError, ReportFormat, TestTidyArgs, TestUnitArgs,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Packages whose unit tests live in the crate itself, rather
+/// than in a `<package>_tests` crate in `tests/unit`.
+const SELF_CONTAINED_TESTS: &[&str] = &[
+    "background_hang_monitor",
+    "gfx",
+    "msg",
+    "net",
+    "net_traits",
+    "selectors",
+    "servo_config",
+    "servo_remutex",
+];
+
+/// Run Servo's unit tests
+///
+/// Each of `test_name` may be a package (`net` or `tests/unit/net`), a
+/// test file in a package (`net/fetch` or `tests/unit/net/fetch.rs`), or
+/// a test name filter. All unit tests are run if nothing is named.
+pub fn test_unit(
+    repo_root: PathBuf,
+    config: Config,
+    test_unit_args: TestUnitArgs,
+    common_args: CommonArgs,
+) -> Result<(), MarsError> {
+    let TestUnitArgs {
+        package,
+        nocapture,
+        format,
+        report,
+        test_name,
+    } = test_unit_args;
+    let CommonArgs {
+        auto_bootstrap,
+        with_layout_2020,
+        ..
+    } = common_args;
+
+    ensure_bootstrapped(&repo_root, &config, &None, auto_bootstrap)?;
+
+    let mut packages = vec![];
+    if let Some(package) = package {
+        packages.push(package);
+    }
+
+    let mut test_patterns = vec![];
+    for test in test_name {
+        let test = test.trim_end_matches('/');
+        let tokens = test.split('/').collect::<Vec<_>>();
+        if tokens.len() == 3 && tokens[0] == "tests" && tokens[1] == "unit" {
+            // tests/unit/<package>
+            packages.push(String::from(tokens[2]));
+        } else if tokens.len() >= 2 {
+            // <package>/<test>, tests/unit/<package>/<test>.rs, or similar
+            packages.push(String::from(tokens[tokens.len() - 2]));
+            let test_prefix = tokens[tokens.len() - 1].trim_end_matches(".rs");
+            test_patterns.push(format!("{}::", test_prefix));
+        } else {
+            test_patterns.push(String::from(test));
+        }
+    }
+
+    let mut self_contained_tests = SELF_CONTAINED_TESTS
+        .iter()
+        .map(|package| String::from(*package))
+        .collect::<Vec<_>>();
+    self_contained_tests.push(String::from(if with_layout_2020 {
+        "layout_2020"
+    } else {
+        "layout_2013"
+    }));
+
+    if packages.is_empty() {
+        let mut unit_tests_dir = repo_root.clone();
+        unit_tests_dir.push("tests");
+        unit_tests_dir.push("unit");
+        let entries = fs::read_dir(&unit_tests_dir).map_err(|error| MarsError::Io {
+            context: format!("read {}", unit_tests_dir.display()),
+            error,
+        })?;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if entry.path().is_dir() {
+                packages.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        packages.extend(self_contained_tests.iter().cloned());
+    }
+    packages.sort();
+    packages.dedup();
+    // mach tests stylo separately, with test-stylo
+    packages.retain(|package| package != "stylo");
+
+    let mut env = build_env(&repo_root, &config, &None, false, false, &[])?;
+    // FIXME: https://github.com/servo/servo/issues/26192
+    if !host_triple().contains("apple-darwin") {
+        env.insert(String::from("RUST_BACKTRACE"), String::from("1"));
+    }
+
+    // TODO translation
+    // mach also puts the gstreamer and mozangle dlls on the PATH on windows
+    // and passes the servo features here
+
+    let mut manifest_path = repo_root.clone();
+    manifest_path.push("Cargo.toml");
+    let mut args = vec![
+        String::from("test"),
+        String::from("--manifest-path"),
+        path_to_string(&manifest_path),
+    ];
+    for package in &packages {
+        args.push(String::from("-p"));
+        if self_contained_tests.contains(package) {
+            args.push(package.clone());
+        } else {
+            args.push(format!("{}_tests", package));
+        }
+    }
+    args.extend(libtest_args(test_patterns, nocapture));
+
+    let (command, args) = rustup_run_command(&repo_root, &config, "cargo", args)?;
+    let mut results = TestResults::default();
+    let status = call_with_lines(command, args, env, false, |line| {
+        println!("{}", line);
+        results.read_line(line);
+    });
+    results.finish_failure();
+
+    results.print_summary();
+
+    if let Some(format) = format {
+        let report = report.unwrap_or_else(|| {
+            let mut report = get_target_dir(&config);
+            report.push(match format {
+                ReportFormat::Junit => "test-unit.xml",
+                ReportFormat::Json => "test-unit.json",
+            });
+
+            report
+        });
+        let contents = match format {
+            ReportFormat::Junit => results.to_junit(),
+            ReportFormat::Json => serde_json::to_string_pretty(&results.suites)
+                .expect("test results are always serializable"),
+        };
+        fs::write(&report, contents).map_err(|error| MarsError::Io {
+            context: format!("write the test report to {}", report.display()),
+            error,
+        })?;
+        println!("Wrote the test report to {}", report.display());
+    }
+
+    status
+}
+
+/// Returns the arguments which `cargo test` passes on to the test
+/// binaries, after a `--`. Cargo only takes a single test name filter
+/// itself, but libtest accepts any number of them.
+fn libtest_args(test_patterns: Vec<String>, nocapture: bool) -> Vec<String> {
+    let mut args = test_patterns;
+    if nocapture {
+        args.push(String::from("--nocapture"));
+    }
+    if !args.is_empty() {
+        args.insert(0, String::from("--"));
+    }
+
+    args
+}
+
+/// Run tidy, Servo's style checks, over the repository
+pub fn test_tidy(repo_root: PathBuf, test_tidy_args: TestTidyArgs) -> Result<(), MarsError> {
+    let TestTidyArgs { only, fix, commits } = test_tidy_args;
+
+    // TODO translation
+    // mach also checks that the WPT manifest is up to date, and runs
+    // rustfmt --check, and only checks files changed since the last
+    // upstream commit unless --all is passed
+
+    let results = tidy::scan(&repo_root, &only, fix)?;
+    for path in &results.fixed {
+        println!("Fixed {}", path);
+    }
+    for error in &results.errors {
+        println!("{}", error);
+    }
+
+    let commit_errors = match commits {
+        Some(base) => tidy::check_commits(&repo_root, base.as_deref())?,
+        None => vec![],
+    };
+    for error in &commit_errors {
+        println!("{}", error);
+    }
+
+    let error_count = results.errors.len() + commit_errors.len();
+    if error_count == 0 {
+        println!("tidy reported no errors.");
+        Ok(())
+    } else {
+        Err(MarsError::CheckFailed(format!(
+            "tidy found {} errors.",
+            error_count
+        )))
+    }
+}
+
+/// Format the Rust source files with rustfmt
+pub fn fmt(repo_root: PathBuf, config: Config, fmt_args: FmtArgs) -> Result<(), MarsError> {
+    let FmtArgs { check, changed } = fmt_args;
+
+    let (command, mut args) = match changed {
+        Some(base) => {
+            let base = base.unwrap_or_else(|| default_git_base(&repo_root));
+            let files = changed_rust_files(&repo_root, &base)?;
+            if files.is_empty() {
+                println!("No Rust files have changed since {}.", base);
+                return Ok(());
+            }
+
+            // Without skip_children, rustfmt also formats the modules
+            // these files declare, even if they haven't changed. The
+            // option is unstable, so it needs a nightly rustfmt.
+            let mut args = vec![String::from("--config"), String::from("skip_children=true")];
+            args.extend(
+                files
+                    .iter()
+                    .map(|file| path_to_string(&repo_root.join(file))),
+            );
+            ("rustfmt", args)
+        }
+        None => (
+            "cargo",
+            vec![
+                String::from("fmt"),
+                String::from("--manifest-path"),
+                path_to_string(&repo_root.join("Cargo.toml")),
+                String::from("--"),
+            ],
+        ),
+    };
+    if check {
+        args.push(String::from("--check"));
+    }
+
+    let result = call_rustup_run(&repo_root, &config, command, args, HashMap::new(), false);
+    match result {
+        // rustfmt exits with 1 when --check finds differences
+        Err(MarsError::ChildFailed { code: Some(1), .. }) if check => {
+            Err(MarsError::CheckFailed(String::from(
+                "rustfmt found files which are not formatted, run `mars fmt` to fix them.",
+            )))
+        }
+        result => result,
+    }
+}
+
+/// Returns the Rust files which have been changed or added since `base`,
+/// including those not committed yet, relative to the repo root.
+fn changed_rust_files(repo_root: &Path, base: &str) -> Result<Vec<String>, MarsError> {
+    let git_files = |args: &[&str]| -> Result<String, MarsError> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo_root)
+            .output()
+            .map_err(|error| MarsError::Io {
+                context: String::from("run git"),
+                error,
+            })?;
+        if !output.status.success() {
+            return Err(MarsError::Config(format!(
+                "Failed to list the files changed since `{}`: {}",
+                base,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    let changed = git_files(&["diff", "--name-only", "-z", "--diff-filter=d", base])?;
+    let untracked = git_files(&["ls-files", "-z", "--others", "--exclude-standard"])?;
+
+    Ok(rust_files(&[&changed, &untracked]))
+}
+
+/// Returns the sorted, unique Rust files in the NUL separated lists of
+/// paths printed by `git -z`.
+fn rust_files(git_outputs: &[&str]) -> Vec<String> {
+    let mut files = git_outputs
+        .iter()
+        .flat_map(|output| output.split('\0'))
+        .filter(|path| path.ends_with(".rs"))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    files.sort();
+    files.dedup();
+
+    files
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+#[derive(Serialize)]
+struct TestCase {
+    name: String,
+    outcome: TestOutcome,
+    /// What the test printed, only captured for failed tests.
+    output: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TestSuite {
+    name: String,
+    duration_secs: Option<f64>,
+    tests: Vec<TestCase>,
+}
+
+/// The results of the test binaries cargo ran, read from
+/// the human readable output of cargo and libtest.
+#[derive(Default)]
+struct TestResults {
+    suites: Vec<TestSuite>,
+    /// The test whose output is being read from the
+    /// `failures:` section at the end of a test binary's output.
+    failure: Option<(String, String)>,
+}
+
+impl TestResults {
+    fn read_line(&mut self, line: &str) {
+        if !self.read_cargo_line(line.trim_end()) {
+            self.read_test_line(line);
+        }
+    }
+
+    /// Reads a line printed by cargo rather than a test binary, returning
+    /// false if it is not the start of a test binary's output.
+    fn read_cargo_line(&mut self, line: &str) -> bool {
+        // cargo right aligns its status to 12 columns, and announces each
+        // test binary as either `Running target/debug/deps/net_tests-0123abcd`
+        // or `Running unittests src/lib.rs (target/debug/deps/net-0123abcd)`
+        let name = if let Some(running) = line.strip_prefix("     Running ") {
+            let binary = match running.rsplit_once(" (") {
+                Some((_, binary)) if binary.ends_with(')') => binary.trim_end_matches(')'),
+                None if !running.contains(' ') => running,
+                _ => return false,
+            };
+            let binary = Path::new(binary).file_stem().map_or_else(
+                || String::from(binary),
+                |stem| stem.to_string_lossy().into_owned(),
+            );
+            match binary.rfind('-') {
+                Some(hash_start) => String::from(&binary[..hash_start]),
+                None => binary,
+            }
+        } else if let Some(crate_name) = line.strip_prefix("   Doc-tests ") {
+            format!("{} (doc)", crate_name)
+        } else {
+            return false;
+        };
+
+        self.finish_failure();
+        self.suites.push(TestSuite {
+            name,
+            duration_secs: None,
+            tests: vec![],
+        });
+
+        true
+    }
+
+    fn read_test_line(&mut self, line: &str) {
+        if line.starts_with("---- ") && line.ends_with(" stdout ----") {
+            self.finish_failure();
+            let name = line
+                .trim_start_matches("---- ")
+                .trim_end_matches(" stdout ----");
+            self.failure = Some((String::from(name), String::new()));
+            return;
+        }
+        if line == "failures:" {
+            self.finish_failure();
+            return;
+        }
+        if let Some((_, output)) = &mut self.failure {
+            output.push_str(line);
+            output.push('\n');
+            return;
+        }
+
+        if line.starts_with("test result: ") {
+            // test result: ok. 3 passed; 0 failed; ...; finished in 0.01s
+            let duration_secs = line
+                .rsplit("finished in ")
+                .next()
+                .and_then(|duration| duration.trim_end_matches('s').parse().ok());
+            if let Some(suite) = self.suites.last_mut() {
+                suite.duration_secs = duration_secs;
+            }
+            return;
+        }
+
+        // test fetch::test_fetch_response_body ... ok
+        if !line.starts_with("test ") {
+            return;
+        }
+        let (name, result) = match line["test ".len()..].rsplit_once(" ... ") {
+            Some(test) => test,
+            None => return,
+        };
+        let outcome = if result == "ok" {
+            TestOutcome::Passed
+        } else if result == "FAILED" {
+            TestOutcome::Failed
+        } else if result.starts_with("ignored") {
+            TestOutcome::Ignored
+        } else {
+            return;
+        };
+        if self.suites.is_empty() {
+            self.suites.push(TestSuite {
+                name: String::from("unknown"),
+                duration_secs: None,
+                tests: vec![],
+            });
+        }
+        let suite = self.suites.last_mut().expect("a suite was just added");
+        suite.tests.push(TestCase {
+            name: String::from(name),
+            outcome,
+            output: None,
+        });
+    }
+
+    /// Record the output of the failed test currently being read.
+    fn finish_failure(&mut self) {
+        let (name, output) = match self.failure.take() {
+            Some(failure) => failure,
+            None => return,
+        };
+        let test = self
+            .suites
+            .last_mut()
+            .and_then(|suite| suite.tests.iter_mut().find(|test| test.name == name));
+        if let Some(test) = test {
+            test.output = Some(String::from(output.trim_end()));
+        }
+    }
+
+    fn count(&self, outcome: TestOutcome) -> usize {
+        self.suites
+            .iter()
+            .flat_map(|suite| &suite.tests)
+            .filter(|test| test.outcome == outcome)
+            .count()
+    }
+
+    fn print_summary(&self) {
+        println!();
+        println!(
+            "Unit tests: {} passed, {} failed, {} ignored",
+            self.count(TestOutcome::Passed),
+            self.count(TestOutcome::Failed),
+            self.count(TestOutcome::Ignored)
+        );
+        for suite in &self.suites {
+            for test in &suite.tests {
+                if test.outcome == TestOutcome::Failed {
+                    println!("    FAILED {} {}", suite.name, test.name);
+                }
+            }
+        }
+    }
+
+    fn to_junit(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+            self.suites
+                .iter()
+                .map(|suite| suite.tests.len())
+                .sum::<usize>(),
+            self.count(TestOutcome::Failed),
+            self.count(TestOutcome::Ignored)
+        ));
+        for suite in &self.suites {
+            let count = |outcome| {
+                suite
+                    .tests
+                    .iter()
+                    .filter(|test| test.outcome == outcome)
+                    .count()
+            };
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+                xml_escape(&suite.name),
+                suite.tests.len(),
+                count(TestOutcome::Failed),
+                count(TestOutcome::Ignored),
+                suite.duration_secs.unwrap_or(0.0)
+            ));
+            for test in &suite.tests {
+                let testcase = format!(
+                    "    <testcase classname=\"{}\" name=\"{}\"",
+                    xml_escape(&suite.name),
+                    xml_escape(&test.name)
+                );
+                match test.outcome {
+                    TestOutcome::Passed => xml.push_str(&format!("{} />\n", testcase)),
+                    TestOutcome::Ignored => {
+                        xml.push_str(&format!("{}>\n      <skipped />\n    </testcase>\n", testcase))
+                    }
+                    TestOutcome::Failed => xml.push_str(&format!(
+                        "{}>\n      <failure message=\"test failed\">{}</failure>\n    </testcase>\n",
+                        testcase,
+                        xml_escape(test.output.as_deref().unwrap_or_default())
+                    )),
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+
+        xml
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_are_passed_to_libtest() {
+        let patterns = || vec![String::from("net::"), String::from("style::")];
+        let strings = |args: &[&str]| {
+            args.iter()
+                .map(|arg| String::from(*arg))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(libtest_args(vec![], false).is_empty());
+        assert_eq!(libtest_args(vec![], true), strings(&["--", "--nocapture"]));
+        assert_eq!(
+            libtest_args(patterns(), false),
+            strings(&["--", "net::", "style::"])
+        );
+        assert_eq!(
+            libtest_args(patterns(), true),
+            strings(&["--", "net::", "style::", "--nocapture"])
+        );
+    }
+
+    #[test]
+    fn changed_rust_files_are_filtered_and_deduplicated() {
+        let changed = "src/lib.rs\0Cargo.toml\0components/net/http.rs\0";
+        let untracked = "components/net/cookie.rs\0README.md\0src/lib.rs\0";
+
+        assert_eq!(
+            rust_files(&[changed, untracked]),
+            [
+                "components/net/cookie.rs",
+                "components/net/http.rs",
+                "src/lib.rs"
+            ]
+        );
+        assert!(rust_files(&["", ""]).is_empty());
+        assert!(rust_files(&["build.rs.orig\0python/rs\0"]).is_empty());
+    }
+
+    /// The output of `cargo test` for two test binaries and a doc test,
+    /// one of which has a failing test.
+    const CARGO_TEST_OUTPUT: &str = "   Compiling net_tests v0.0.1 (/servo/tests/unit/net)
+    Finished test [unoptimized + debuginfo] target(s) in 4.20s
+     Running unittests src/lib.rs (target/debug/deps/net-0123abcd)
+
+running 3 tests
+test fetch::test_fetch ... ok
+test fetch::test_cors ... FAILED
+test http::test_slow ... ignored
+
+failures:
+
+---- fetch::test_cors stdout ----
+     Running a request to http://localhost/
+Running target/debug/deps/fake-0123abcd
+thread 'fetch::test_cors' panicked at 'assertion failed: `(left == right)`
+  left: `<html>`,
+ right: `a & \"b\"`', tests/unit/net/fetch.rs:10:5
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    fetch::test_cors
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.52s
+
+     Running target/debug/deps/style_tests-89abcdef
+
+running 1 test
+test parsing::test_parse ... ok
+
+test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.01s
+
+   Doc-tests net
+
+running 0 tests
+
+test result: ok. 0 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+";
+
+    #[test]
+    fn cargo_test_output_to_junit() {
+        let mut results = TestResults::default();
+        for line in CARGO_TEST_OUTPUT.lines() {
+            results.read_line(line);
+        }
+        results.finish_failure();
+
+        assert_eq!(
+            results.to_junit(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="4" failures="1" skipped="1">
+  <testsuite name="net" tests="3" failures="1" skipped="1" time="0.52">
+    <testcase classname="net" name="fetch::test_fetch" />
+    <testcase classname="net" name="fetch::test_cors">
+      <failure message="test failed">     Running a request to http://localhost/
+Running target/debug/deps/fake-0123abcd
+thread 'fetch::test_cors' panicked at 'assertion failed: `(left == right)`
+  left: `&lt;html&gt;`,
+ right: `a &amp; &quot;b&quot;`', tests/unit/net/fetch.rs:10:5
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace</failure>
+    </testcase>
+    <testcase classname="net" name="http::test_slow">
+      <skipped />
+    </testcase>
+  </testsuite>
+  <testsuite name="style_tests" tests="1" failures="0" skipped="0" time="0.01">
+    <testcase classname="style_tests" name="parsing::test_parse" />
+  </testsuite>
+  <testsuite name="net (doc)" tests="0" failures="0" skipped="0" time="0">
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn test_binary_names() {
+        let mut results = TestResults::default();
+        for line in &[
+            "     Running target/debug/deps/gfx_tests-0123abcd.exe",
+            "     Running tests/layout.rs (target/debug/deps/layout-0123abcd)",
+            "     Running unittests (target/debug/deps/script-0123abcd)",
+            "Running target/debug/deps/net_tests-0123abcd",
+            "     Running the test server",
+        ] {
+            results.read_line(line);
+        }
+        let names = results
+            .suites
+            .iter()
+            .map(|suite| suite.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["gfx_tests", "layout", "script"]);
+    }
+}
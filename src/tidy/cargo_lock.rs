@@ -0,0 +1,343 @@
+use super::{FileCheck, TidyConfig};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use toml::Value;
+
+const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+/// Checks that no package is in `Cargo.lock` at more than one version,
+/// unless it is listed in `packages` in the `[ignore]` section of
+/// `servo-tidy.toml`.
+///
+/// Each duplicate version is reported along with the packages which
+/// depend on it directly, and the workspace crates which pull it in.
+pub struct CargoLockCheck<'a> {
+    pub config: &'a TidyConfig,
+}
+
+/// A package in `Cargo.lock`.
+struct Package {
+    name: String,
+    version: String,
+    /// `None` for the crates in the workspace.
+    source: Option<String>,
+    dependencies: Vec<String>,
+}
+
+impl FileCheck for CargoLockCheck<'_> {
+    fn name(&self) -> &'static str {
+        "cargo-lock"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        // Only the workspace lockfile, not those of vendored or test crates
+        path == "Cargo.lock"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+
+        let lock_file = match contents.parse::<Value>() {
+            Ok(lock_file) => lock_file,
+            Err(error) => return vec![(1, format!("failed to parse Cargo.lock: {}", error))],
+        };
+        let packages = read_packages(&lock_file);
+
+        let mut packages_by_name: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, package) in packages.iter().enumerate() {
+            packages_by_name
+                .entry(package.name.as_str())
+                .or_default()
+                .push(index);
+        }
+
+        let exceptions = &self.config.ignored_packages;
+        for name in exceptions {
+            if !packages_by_name.contains_key(name.as_str()) {
+                errors.push((
+                    1,
+                    format!(
+                        "duplicates are allowed for `{}` but it is not a dependency",
+                        name
+                    ),
+                ));
+            }
+        }
+
+        let dependents = reverse_dependencies(&packages);
+        for (name, versions) in &packages_by_name {
+            let has_duplicates = versions.len() > 1;
+            let duplicates_allowed = exceptions.iter().any(|exception| exception == name);
+            if has_duplicates == duplicates_allowed {
+                continue;
+            }
+
+            let mut message = if duplicates_allowed {
+                format!(
+                    "duplicates for `{}` are allowed, but only single version found",
+                    name
+                )
+            } else {
+                format!("duplicate versions for package `{}`", name)
+            };
+            for &index in versions {
+                let package = &packages[index];
+                message.push_str(&format!(
+                    "\n\tversion {} from {}",
+                    package.version,
+                    short_source(&package.source)
+                ));
+                let direct = dependents[index]
+                    .iter()
+                    .map(|&dependent| {
+                        format!(
+                            "{} {}",
+                            packages[dependent].name, packages[dependent].version
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if !direct.is_empty() {
+                    message.push_str(&format!("\n\t\tdepended on by {}", direct.join(", ")));
+                }
+                let workspace = workspace_dependents(&packages, &dependents, index);
+                if !workspace.is_empty() {
+                    message.push_str(&format!(
+                        "\n\t\tpulled in by workspace crates {}",
+                        workspace.into_iter().collect::<Vec<_>>().join(", ")
+                    ));
+                }
+            }
+
+            let line = package_line(contents, name);
+            errors.push((line, message));
+        }
+
+        errors
+    }
+}
+
+fn read_packages(lock_file: &Value) -> Vec<Package> {
+    let packages = match lock_file.get("package").and_then(Value::as_array) {
+        Some(packages) => packages,
+        None => return vec![],
+    };
+
+    packages
+        .iter()
+        // Packages replaced with [replace] in Cargo.toml are listed twice
+        .filter(|package| package.get("replace").is_none())
+        .map(|package| {
+            let string = |key: &str| package.get(key).and_then(Value::as_str).map(String::from);
+            Package {
+                name: string("name").unwrap_or_default(),
+                version: string("version").unwrap_or_default(),
+                source: string("source"),
+                dependencies: package
+                    .get("dependencies")
+                    .and_then(Value::as_array)
+                    .map(|dependencies| {
+                        dependencies
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .map(String::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Returns, for each package, the indices of the packages which depend on it.
+fn reverse_dependencies(packages: &[Package]) -> Vec<BTreeSet<usize>> {
+    let mut dependents = vec![BTreeSet::new(); packages.len()];
+    let mut packages_by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, package) in packages.iter().enumerate() {
+        packages_by_name
+            .entry(package.name.as_str())
+            .or_default()
+            .push(index);
+    }
+
+    for (dependent, package) in packages.iter().enumerate() {
+        for dependency in &package.dependencies {
+            // Dependencies are written as `name`, `name version`
+            // or `name version (source)`, depending on whether the
+            // name alone is enough to tell which package is meant.
+            let mut parts = dependency.split_whitespace();
+            let name = parts.next().unwrap_or_default();
+            let version = parts.next();
+            let source = parts
+                .next()
+                .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+            let candidates = packages_by_name.get(name).map(Vec::as_slice).unwrap_or(&[]);
+            for &index in candidates {
+                let candidate = &packages[index];
+                let version_matches = version.is_none_or(|version| version == candidate.version);
+                let source_matches =
+                    source.is_none_or(|source| Some(source) == candidate.source.as_deref());
+                if version_matches && source_matches {
+                    dependents[index].insert(dependent);
+                }
+            }
+        }
+    }
+
+    dependents
+}
+
+/// Returns the names of the workspace crates which
+/// depend on the package at `index`, directly or not.
+fn workspace_dependents(
+    packages: &[Package],
+    dependents: &[BTreeSet<usize>],
+    index: usize,
+) -> BTreeSet<String> {
+    let mut workspace = BTreeSet::new();
+    let mut seen = BTreeSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(index);
+    while let Some(index) = queue.pop_front() {
+        for &dependent in &dependents[index] {
+            if !seen.insert(dependent) {
+                continue;
+            }
+            if packages[dependent].source.is_none() {
+                workspace.insert(packages[dependent].name.clone());
+            }
+            queue.push_back(dependent);
+        }
+    }
+
+    workspace
+}
+
+fn short_source(source: &Option<String>) -> String {
+    match source.as_deref() {
+        None => String::from("the workspace"),
+        Some(CRATES_IO) => String::from("crates.io"),
+        Some(source) => {
+            let source = source.split('#').next().unwrap_or(source);
+            String::from(source.trim_start_matches("git+"))
+        }
+    }
+}
+
+/// Returns the line of the first entry for the package `name`.
+fn package_line(contents: &str, name: &str) -> usize {
+    let name_line = format!("name = \"{}\"", name);
+    contents
+        .lines()
+        .position(|line| line.trim() == name_line)
+        .map_or(1, |index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK_FILE: &str = r#"
+[[package]]
+name = "a"
+version = "0.1.0"
+dependencies = [
+ "bitflags 1.2.1",
+]
+
+[[package]]
+name = "b"
+version = "0.1.0"
+dependencies = [
+ "bitflags 0.7.0",
+ "log",
+]
+
+[[package]]
+name = "bitflags"
+version = "0.7.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "bitflags"
+version = "1.2.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "log"
+version = "0.4.8"
+source = "git+https://github.com/rust-lang/log#0123abc"
+
+[[package]]
+name = "servo"
+version = "0.0.1"
+dependencies = [
+ "a",
+ "b",
+]
+"#;
+
+    fn check(config: &str, contents: &str) -> Vec<(usize, String)> {
+        let config = TidyConfig::from_toml(config);
+        CargoLockCheck { config: &config }.check("Cargo.lock", contents)
+    }
+
+    #[test]
+    fn duplicate_versions() {
+        assert_eq!(
+            check("", LOCK_FILE),
+            [(
+                18,
+                String::from(
+                    "duplicate versions for package `bitflags`\
+                     \n\tversion 0.7.0 from crates.io\
+                     \n\t\tdepended on by b 0.1.0\
+                     \n\t\tpulled in by workspace crates b, servo\
+                     \n\tversion 1.2.1 from crates.io\
+                     \n\t\tdepended on by a 0.1.0\
+                     \n\t\tpulled in by workspace crates a, servo"
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn ignored_packages() {
+        assert!(check("[ignore]\npackages = [\"bitflags\"]", LOCK_FILE).is_empty());
+        assert_eq!(
+            check(
+                "[ignore]\npackages = [\"bitflags\", \"log\", \"url\"]",
+                LOCK_FILE
+            ),
+            [
+                (
+                    1,
+                    String::from("duplicates are allowed for `url` but it is not a dependency")
+                ),
+                (
+                    28,
+                    String::from(
+                        "duplicates for `log` are allowed, but only single version found\
+                         \n\tversion 0.4.8 from https://github.com/rust-lang/log\
+                         \n\t\tdepended on by b 0.1.0\
+                         \n\t\tpulled in by workspace crates b, servo"
+                    )
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_workspace_lock_file_is_checked() {
+        let config = TidyConfig::from_toml("");
+        let check = CargoLockCheck { config: &config };
+        assert!(check.applies_to("Cargo.lock"));
+        assert!(!check.applies_to("support/crown/Cargo.lock"));
+        assert!(!check.applies_to("tests/Cargo.lock"));
+    }
+
+    #[test]
+    fn invalid_lock_file() {
+        let errors = check("", "[[package]");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.starts_with("failed to parse Cargo.lock"));
+    }
+}
@@ -0,0 +1,211 @@
+use super::{
+    data_files::{toml_lines, TomlLine},
+    FileCheck,
+};
+
+/// Checks that the dependencies in each dependency table of a
+/// `Cargo.toml` are sorted by name.
+///
+/// Blank lines split a table into groups which are sorted separately.
+/// `--fix` sorts them, moving the comments above each dependency along
+/// with it.
+pub struct CargoTomlCheck;
+
+/// A dependency, along with the comments above it.
+struct Entry<'a> {
+    name: &'a str,
+    /// The index of the line the dependency's name is on.
+    key_line: usize,
+    lines: Vec<&'a str>,
+}
+
+/// A group of dependencies which should be sorted, with
+/// any comments which follow the last dependency.
+struct Group<'a> {
+    entries: Vec<Entry<'a>>,
+    trailing: Vec<&'a str>,
+}
+
+impl FileCheck for CargoTomlCheck {
+    fn name(&self) -> &'static str {
+        "cargo-toml"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        path.rsplit('/').next() == Some("Cargo.toml")
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+        for (_, group) in dependency_groups(contents) {
+            for pair in group.entries.windows(2) {
+                if pair[1].name < pair[0].name {
+                    errors.push((
+                        pair[1].key_line + 1,
+                        format!(
+                            "dependencies are not in alphabetical order, expected `{}` before `{}`",
+                            pair[1].name, pair[0].name
+                        ),
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+
+    fn fix(&self, _path: &str, contents: &str) -> Option<String> {
+        let lines = contents.lines().collect::<Vec<_>>();
+        let mut fixed: Vec<&str> = vec![];
+        let mut next_line = 0;
+        for (start, mut group) in dependency_groups(contents) {
+            fixed.extend(&lines[next_line..start]);
+            group.entries.sort_by(|a, b| a.name.cmp(b.name));
+            for entry in &group.entries {
+                fixed.extend(&entry.lines);
+            }
+            fixed.extend(&group.trailing);
+            next_line = start
+                + group
+                    .entries
+                    .iter()
+                    .map(|entry| entry.lines.len())
+                    .sum::<usize>()
+                + group.trailing.len();
+        }
+        fixed.extend(&lines[next_line..]);
+
+        let mut fixed = fixed.join("\n");
+        if contents.ends_with('\n') {
+            fixed.push('\n');
+        }
+
+        Some(fixed)
+    }
+}
+
+fn is_dependency_table(name: &str) -> bool {
+    ["dependencies", "dev-dependencies", "build-dependencies"]
+        .iter()
+        .any(|table| name == *table || name.ends_with(&format!(".{}", table)))
+}
+
+/// Returns the groups of dependencies in `contents`, along
+/// with the index of the line each group starts on.
+fn dependency_groups(contents: &str) -> Vec<(usize, Group<'_>)> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let mut groups = vec![];
+    let mut in_dependencies = false;
+    let mut group: Option<(usize, Group)> = None;
+    // Comments not yet part of an entry
+    let mut pending: Vec<&str> = vec![];
+
+    for (index, toml_line) in toml_lines(contents).into_iter().enumerate() {
+        let line = lines[index];
+        match toml_line {
+            TomlLine::Table { name, .. } => {
+                finish_group(&mut group, &mut pending, &mut groups);
+                in_dependencies = is_dependency_table(name);
+            }
+            _ if !in_dependencies => {}
+            TomlLine::Key(name) => {
+                let (_, current) = group.get_or_insert_with(|| {
+                    (
+                        index - pending.len(),
+                        Group {
+                            entries: vec![],
+                            trailing: vec![],
+                        },
+                    )
+                });
+                let mut entry_lines = std::mem::take(&mut pending);
+                entry_lines.push(line);
+                current.entries.push(Entry {
+                    name,
+                    key_line: index,
+                    lines: entry_lines,
+                });
+            }
+            // A value split over several lines belongs to the entry
+            // before, along with any comments inside it
+            TomlLine::Continuation => match group
+                .as_mut()
+                .and_then(|(_, group)| group.entries.last_mut())
+            {
+                Some(entry) => entry.lines.push(line),
+                None => pending.push(line),
+            },
+            TomlLine::Other if line.trim().is_empty() => {
+                finish_group(&mut group, &mut pending, &mut groups);
+            }
+            TomlLine::Other => pending.push(line),
+        }
+    }
+    finish_group(&mut group, &mut pending, &mut groups);
+
+    groups
+}
+
+/// Adds `group` to `groups`, if there is one, along
+/// with the comments in `pending` which follow it.
+fn finish_group<'a>(
+    group: &mut Option<(usize, Group<'a>)>,
+    pending: &mut Vec<&'a str>,
+    groups: &mut Vec<(usize, Group<'a>)>,
+) {
+    if let Some((start, mut group)) = group.take() {
+        group.trailing.append(pending);
+        groups.push((start, group));
+    }
+    pending.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsorted_dependencies() {
+        let contents = "[package]\nname = \"servo\"\n\n\
+                        [dependencies]\nb = \"1\"\na = \"1\"\n\nd = \"1\"\nc = \"1\"\n\n\
+                        [target.'cfg(windows)'.dev-dependencies]\nf = \"1\"\ne = \"1\"\n\n\
+                        [features]\nz = []\ny = []\n";
+        let error = |line: usize, first: &str, second: &str| {
+            (
+                line,
+                format!(
+                    "dependencies are not in alphabetical order, expected `{}` before `{}`",
+                    first, second
+                ),
+            )
+        };
+        assert_eq!(
+            CargoTomlCheck.check("Cargo.toml", contents),
+            [error(6, "a", "b"), error(9, "c", "d"), error(13, "e", "f")]
+        );
+    }
+
+    #[test]
+    fn fix_moves_comments_and_values_with_their_dependency() {
+        let contents = "[dependencies]\n\
+                        # The second dependency\n\
+                        c = \"1\"\n\
+                        b = [\n  # note\n  \"x\",\n]\n\
+                        a = \"1\"\n\
+                        # After the group\n\
+                        \n\
+                        [dev-dependencies]\ny = \"1\"\nx = \"1\"\n";
+        let fixed = CargoTomlCheck.fix("Cargo.toml", contents);
+        let expected = "[dependencies]\n\
+                        a = \"1\"\n\
+                        b = [\n  # note\n  \"x\",\n]\n\
+                        # The second dependency\n\
+                        c = \"1\"\n\
+                        # After the group\n\
+                        \n\
+                        [dev-dependencies]\nx = \"1\"\ny = \"1\"\n";
+        assert_eq!(fixed.as_deref(), Some(expected));
+        assert!(CargoTomlCheck.check("Cargo.toml", expected).is_empty());
+        assert!(expected.parse::<toml::Value>().is_ok());
+    }
+}
@@ -0,0 +1,142 @@
+use super::TidyConfig;
+use crate::{command_base::default_git_base, MarsError};
+use std::{fmt, path::Path, process::Command};
+
+/// A problem with a commit, found by `check_commits`.
+pub struct CommitError {
+    /// The abbreviated hash of the commit.
+    pub commit: String,
+    pub subject: String,
+    pub message: String,
+}
+
+impl fmt::Display for CommitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "commit {} \"{}\": {}",
+            self.commit, self.subject, self.message
+        )
+    }
+}
+
+/// Checks the commits on HEAD which aren't on `base`, or on the
+/// upstream of the current branch if no base is given.
+///
+/// Merge commits, fixup and squash commits, commits without an author
+/// email, and subject lines longer than `commit-subject-length` in the
+/// `[configs]` section of `servo-tidy.toml` are reported.
+pub fn check_commits(repo_root: &Path, base: Option<&str>) -> Result<Vec<CommitError>, MarsError> {
+    let config = TidyConfig::new(repo_root)?;
+    let base = match base {
+        Some(base) => String::from(base),
+        None => default_git_base(repo_root),
+    };
+
+    let output = Command::new("git")
+        .args([
+            "log",
+            "--format=%h%x1f%P%x1f%ae%x1f%s",
+            &format!("{}..HEAD", base),
+        ])
+        .current_dir(repo_root)
+        .output()
+        .map_err(|error| MarsError::Io {
+            context: String::from("run git log"),
+            error,
+        })?;
+    if !output.status.success() {
+        return Err(MarsError::Config(format!(
+            "Failed to list the commits since `{}`: {}",
+            base,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(commit_errors(
+        &String::from_utf8_lossy(&output.stdout),
+        &base,
+        config.commit_subject_length,
+    ))
+}
+
+/// Returns the problems with the commits in `log`, the output of
+/// `git log --format=%h%x1f%P%x1f%ae%x1f%s`.
+fn commit_errors(log: &str, base: &str, commit_subject_length: usize) -> Vec<CommitError> {
+    let mut errors = vec![];
+    for line in log.lines() {
+        let mut fields = line.splitn(4, '\u{1f}');
+        let commit = fields.next().unwrap_or_default();
+        let parents = fields.next().unwrap_or_default();
+        let email = fields.next().unwrap_or_default();
+        let subject = fields.next().unwrap_or_default();
+
+        let mut messages = vec![];
+        if parents.split_whitespace().count() > 1 {
+            messages.push(format!(
+                "merge commits are not allowed, rebase onto {} instead",
+                base
+            ));
+        }
+        if ["fixup!", "squash!", "amend!"]
+            .iter()
+            .any(|prefix| subject.starts_with(prefix))
+        {
+            messages.push(String::from("squash this commit before pushing"));
+        }
+        if email.trim().is_empty() {
+            messages.push(String::from("the author has no email address"));
+        }
+        let subject_length = subject.chars().count();
+        if subject_length > commit_subject_length {
+            messages.push(format!(
+                "the subject is {} characters long, at most {} are allowed",
+                subject_length, commit_subject_length
+            ));
+        }
+
+        errors.extend(messages.into_iter().map(|message| CommitError {
+            commit: String::from(commit),
+            subject: String::from(subject),
+            message,
+        }));
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(log: &str) -> Vec<String> {
+        commit_errors(log, "origin/main", 20)
+            .iter()
+            .map(CommitError::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn good_commits() {
+        let log = "abc1234\u{1f}def5678\u{1f}a@servo.org\u{1f}Fix layout\n\
+                   def5678\u{1f}0123abc\u{1f}b@servo.org\u{1f}Exactly twenty chars\n";
+        assert!(errors(log).is_empty());
+        assert!(errors("").is_empty());
+    }
+
+    #[test]
+    fn bad_commits() {
+        let log = "abc1234\u{1f}def5678 0123abc\u{1f}a@servo.org\u{1f}Merge main\n\
+                   def5678\u{1f}0123abc\u{1f}\u{1f}fixup! Fix layout\n\
+                   0123abc\u{1f}4567def\u{1f}c@servo.org\u{1f}Fix layout: a subject | with bars\n";
+        assert_eq!(
+            errors(log),
+            [
+                "commit abc1234 \"Merge main\": merge commits are not allowed, rebase onto origin/main instead",
+                "commit def5678 \"fixup! Fix layout\": squash this commit before pushing",
+                "commit def5678 \"fixup! Fix layout\": the author has no email address",
+                "commit 0123abc \"Fix layout: a subject | with bars\": the subject is 33 characters long, at most 20 are allowed",
+            ]
+        );
+    }
+}
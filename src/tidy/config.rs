@@ -0,0 +1,105 @@
+use crate::MarsError;
+use serde::Deserialize;
+use std::{fs::read_to_string, path::Path};
+
+/// Representation of a valid `servo-tidy.toml` file.
+///
+/// All sections of the file are optional, and all fields
+/// in each section are optional.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TidyConfigFile {
+    #[serde(default)]
+    configs: TidyConfigFileConfigs,
+    #[serde(default)]
+    ignore: TidyConfigFileIgnore,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TidyConfigFileConfigs {
+    skip_check_length: Option<bool>,
+    skip_check_licenses: Option<bool>,
+    check_alphabetical_order: Option<bool>,
+    commit_subject_length: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TidyConfigFileIgnore {
+    packages: Option<Vec<String>>,
+    files: Option<Vec<String>>,
+    directories: Option<Vec<String>>,
+}
+
+/// The settings from the `servo-tidy.toml` file at the root of
+/// the repository, with defaults filled in.
+pub struct TidyConfig {
+    pub skip_check_length: bool,
+    pub skip_check_licenses: bool,
+    pub check_alphabetical_order: bool,
+    /// The longest commit subject line `--commits` allows.
+    pub commit_subject_length: usize,
+    /// Packages which are allowed to be in Cargo.lock more than once.
+    pub ignored_packages: Vec<String>,
+    /// Files which are not checked, relative to the repo root.
+    pub ignored_files: Vec<String>,
+    /// Directories whose contents are not checked, relative to the repo root.
+    pub ignored_directories: Vec<String>,
+}
+
+impl TidyConfig {
+    pub fn new(repo_root: &Path) -> Result<Self, MarsError> {
+        let path = repo_root.join("servo-tidy.toml");
+        let config_file = match read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|error| {
+                MarsError::Config(format!("Failed to parse {}: {}", path.display(), error))
+            })?,
+            Err(_) => TidyConfigFile::default(),
+        };
+
+        Ok(Self::resolve(config_file))
+    }
+
+    /// Parses `contents` as a `servo-tidy.toml` file.
+    #[cfg(test)]
+    pub fn from_toml(contents: &str) -> Self {
+        let config_file = toml::from_str(contents).expect("test config should be valid");
+
+        Self::resolve(config_file)
+    }
+
+    /// Fills in the defaults for the keys missing from `config_file`.
+    fn resolve(config_file: TidyConfigFile) -> Self {
+        // Entries are written like `./components/script`
+        let normalize = |paths: Option<Vec<String>>| {
+            paths
+                .unwrap_or_default()
+                .into_iter()
+                .map(|path| String::from(path.trim_start_matches("./").trim_end_matches('/')))
+                .collect()
+        };
+
+        TidyConfig {
+            skip_check_length: config_file.configs.skip_check_length.unwrap_or(false),
+            skip_check_licenses: config_file.configs.skip_check_licenses.unwrap_or(false),
+            check_alphabetical_order: config_file.configs.check_alphabetical_order.unwrap_or(true),
+            commit_subject_length: config_file.configs.commit_subject_length.unwrap_or(72),
+            ignored_packages: config_file.ignore.packages.unwrap_or_default(),
+            ignored_files: normalize(config_file.ignore.files),
+            ignored_directories: normalize(config_file.ignore.directories),
+        }
+    }
+
+    /// Returns true if `path`, which is relative to the repo root and
+    /// uses `/` as a separator, should not be checked.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        // mach ignores hidden files and directories at the top
+        // level of the repository, such as .git
+        path.starts_with('.')
+            || self.ignored_files.iter().any(|file| file == path)
+            || self.ignored_directories.iter().any(|directory| {
+                path.starts_with(directory.as_str()) && path[directory.len()..].starts_with('/')
+            })
+    }
+}
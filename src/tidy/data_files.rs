@@ -0,0 +1,539 @@
+use super::{extension, FileCheck};
+use std::collections::HashMap;
+
+/// Tracks the keys defined in each of a stack of nested
+/// mappings, to report keys which are defined twice.
+#[derive(Default)]
+pub struct DuplicateKeys {
+    scopes: Vec<HashMap<String, usize>>,
+    pub errors: Vec<(usize, String)>,
+}
+
+impl DuplicateKeys {
+    /// Start a new mapping, nested in the current one.
+    pub fn push(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// End the current mapping.
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+    }
+
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Record that `key` is defined on `line` of the current mapping.
+    pub fn key(&mut self, key: &str, line: usize) {
+        self.define(&format!("key `{}`", key), line);
+    }
+
+    /// Record that the section `[name]` starts on `line` of the current mapping.
+    pub fn section(&mut self, name: &str, line: usize) {
+        self.define(&format!("section `[{}]`", name), line);
+    }
+
+    fn define(&mut self, key: &str, line: usize) {
+        if self.scopes.is_empty() {
+            self.push();
+        }
+        let scope = self.scopes.last_mut().expect("there is always a scope");
+        match scope.get(key) {
+            Some(first_line) => self.errors.push((
+                line,
+                format!("duplicate {}, first defined on line {}", key, first_line),
+            )),
+            None => {
+                scope.insert(String::from(key), line);
+            }
+        }
+    }
+}
+
+/// Checks that JSON files parse, and that no object has the same key twice.
+pub struct JsonCheck;
+
+impl FileCheck for JsonCheck {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        extension(path) == "json"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        if let Err(error) = serde_json::from_str::<serde_json::Value>(contents) {
+            return vec![(error.line().max(1), format!("invalid JSON: {}", error))];
+        }
+
+        json_duplicate_keys(contents)
+    }
+}
+
+/// Returns the keys which are defined twice in an object in `contents`,
+/// which must be valid JSON.
+fn json_duplicate_keys(contents: &str) -> Vec<(usize, String)> {
+    let mut keys = DuplicateKeys::default();
+    // Whether each of the open brackets is an object, rather than an array
+    let mut in_object = vec![];
+    let bytes = contents.as_bytes();
+    let mut line = 1;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\n' => line += 1,
+            b'{' => {
+                in_object.push(true);
+                keys.push();
+            }
+            b'[' => in_object.push(false),
+            b'}' => {
+                in_object.pop();
+                keys.pop();
+            }
+            b']' => {
+                in_object.pop();
+            }
+            b'"' => {
+                let start = index + 1;
+                index += 1;
+                while index < bytes.len() && bytes[index] != b'"' {
+                    if bytes[index] == b'\\' {
+                        index += 1;
+                    }
+                    index += 1;
+                }
+                let string = &contents[start..index.min(bytes.len())];
+
+                // A string followed by a colon in an object is a key
+                let next = bytes
+                    .get(index + 1..)
+                    .unwrap_or_default()
+                    .iter()
+                    .find(|byte| !byte.is_ascii_whitespace());
+                if next == Some(&b':') && in_object.last() == Some(&true) {
+                    keys.key(string, line);
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+
+    keys.errors
+}
+
+/// Checks that TOML files parse, and that no table or key is defined twice.
+pub struct TomlCheck;
+
+impl FileCheck for TomlCheck {
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        extension(path) == "toml"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        // The toml crate rejects duplicate keys too, but only
+        // reports where the second definition is.
+        let duplicates = toml_duplicate_keys(contents);
+        if !duplicates.is_empty() {
+            return duplicates;
+        }
+
+        match contents.parse::<toml::Value>() {
+            Ok(_) => vec![],
+            Err(error) => {
+                let line = error.line_col().map_or(1, |(line, _)| line + 1);
+                vec![(line, format!("invalid TOML: {}", error))]
+            }
+        }
+    }
+}
+
+/// The kinds of line in a TOML file.
+pub enum TomlLine<'a> {
+    /// `[name]`, or `[[name]]` for an array of tables.
+    Table {
+        name: &'a str,
+        is_array: bool,
+    },
+    Key(&'a str),
+    /// A line continuing a value started on an earlier line, such as
+    /// an element of an array split over lines, or a comment between them.
+    Continuation,
+    /// A blank line, or a comment.
+    Other,
+}
+
+/// Splits `contents` into `TomlLine`s, one per line.
+pub fn toml_lines(contents: &str) -> Vec<TomlLine<'_>> {
+    let mut state = ValueState::default();
+    contents
+        .lines()
+        .map(|line| {
+            if state.is_open() {
+                state.read(line);
+                return TomlLine::Continuation;
+            }
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                TomlLine::Other
+            } else if trimmed.starts_with('[') {
+                let is_array = trimmed.starts_with("[[");
+                let name = trimmed
+                    .split('#')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .trim();
+                TomlLine::Table { name, is_array }
+            } else {
+                match trimmed.split_once('=') {
+                    Some((key, value)) => {
+                        state.read(value);
+                        TomlLine::Key(key.trim().trim_matches('"'))
+                    }
+                    None => TomlLine::Other,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Tracks whether a TOML value is still open at the end of a line,
+/// because it is an array, inline table or string split over lines.
+#[derive(Default)]
+struct ValueState {
+    depth: usize,
+    /// The delimiter of the multi-line string being read, if any.
+    string: Option<&'static str>,
+}
+
+impl ValueState {
+    fn is_open(&self) -> bool {
+        self.depth > 0 || self.string.is_some()
+    }
+
+    fn read(&mut self, line: &str) {
+        let mut rest = line;
+        while !rest.is_empty() {
+            if let Some(delimiter) = self.string {
+                match rest.find(delimiter) {
+                    Some(end) => {
+                        rest = &rest[end + delimiter.len()..];
+                        self.string = None;
+                    }
+                    None => return,
+                }
+                continue;
+            }
+
+            if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+                self.string = Some(if rest.starts_with('"') {
+                    "\"\"\""
+                } else {
+                    "'''"
+                });
+                rest = &rest[3..];
+                continue;
+            }
+            let c = rest.chars().next().expect("rest is not empty");
+            match c {
+                '#' => return,
+                '[' | '{' => self.depth += 1,
+                ']' | '}' => self.depth = self.depth.saturating_sub(1),
+                '"' | '\'' => {
+                    // Skip over single line strings
+                    let mut escaped = false;
+                    let mut end = rest.len();
+                    for (index, next) in rest.char_indices().skip(1) {
+                        if escaped {
+                            escaped = false;
+                        } else if next == '\\' && c == '"' {
+                            escaped = true;
+                        } else if next == c {
+                            end = index + 1;
+                            break;
+                        }
+                    }
+                    rest = &rest[end..];
+                    continue;
+                }
+                _ => {}
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+}
+
+fn toml_duplicate_keys(contents: &str) -> Vec<(usize, String)> {
+    let mut errors = vec![];
+    let mut tables = HashMap::new();
+    let mut keys = DuplicateKeys::default();
+    keys.push();
+    for (index, line) in toml_lines(contents).into_iter().enumerate() {
+        let line_number = index + 1;
+        match line {
+            TomlLine::Table { name, is_array } => {
+                keys.pop();
+                keys.push();
+                // Each [[name]] starts a new table in an array
+                if is_array {
+                    continue;
+                }
+                match tables.get(name) {
+                    Some(first_line) => errors.push((
+                        line_number,
+                        format!(
+                            "duplicate table `[{}]`, first defined on line {}",
+                            name, first_line
+                        ),
+                    )),
+                    None => {
+                        tables.insert(name, line_number);
+                    }
+                }
+            }
+            TomlLine::Key(key) => keys.key(key, line_number),
+            TomlLine::Continuation | TomlLine::Other => {}
+        }
+    }
+    errors.extend(keys.errors);
+    errors.sort();
+
+    errors
+}
+
+/// Checks that YAML files parse, and that no mapping has the same key twice.
+pub struct YamlCheck;
+
+impl FileCheck for YamlCheck {
+    fn name(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        let extension = extension(path);
+        extension == "yml" || extension == "yaml"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let duplicates = yaml_duplicate_keys(contents);
+        if !duplicates.is_empty() {
+            return duplicates;
+        }
+
+        match serde_yaml::from_str::<serde_yaml::Value>(contents) {
+            Ok(_) => vec![],
+            Err(error) => {
+                let line = error.location().map_or(1, |location| location.line());
+                vec![(line, format!("invalid YAML: {}", error))]
+            }
+        }
+    }
+}
+
+/// Returns the keys which are defined twice in a block mapping in `contents`.
+///
+/// Mappings are told apart by their indentation, and each item of
+/// a sequence starts a new mapping. Flow mappings aren't checked.
+fn yaml_duplicate_keys(contents: &str) -> Vec<(usize, String)> {
+    let mut keys = DuplicateKeys::default();
+    // The indentation of each of the mappings in `keys`
+    let mut indents: Vec<usize> = vec![];
+    // The indentation of the key a block scalar belongs to
+    let mut block_scalar: Option<usize> = None;
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim_start();
+        let mut indent = line.len() - trimmed.len();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(key_indent) = block_scalar {
+            if indent > key_indent {
+                continue;
+            }
+            block_scalar = None;
+        }
+        if trimmed.starts_with("---") || trimmed.starts_with("...") {
+            while keys.depth() > 0 {
+                keys.pop();
+            }
+            indents.clear();
+            continue;
+        }
+
+        let mut content = trimmed;
+        let mut starts_item = false;
+        while let Some(item) = content.strip_prefix("- ") {
+            // The item's content is as indented as if the dash were a space
+            indent += content.len() - item.trim_start().len();
+            content = item.trim_start();
+            starts_item = true;
+        }
+
+        while indents.last().is_some_and(|&last| last > indent)
+            || (starts_item && indents.last() == Some(&indent))
+        {
+            indents.pop();
+            keys.pop();
+        }
+
+        let key = match yaml_key(content) {
+            Some(key) => key,
+            None => continue,
+        };
+        if indents.last() != Some(&indent) {
+            indents.push(indent);
+            keys.push();
+        }
+        keys.key(key, line_number);
+
+        let value = content
+            .split_once(": ")
+            .map_or("", |(_, value)| value.trim());
+        if value.starts_with('|') || value.starts_with('>') {
+            block_scalar = Some(indent);
+        }
+    }
+
+    keys.errors
+}
+
+/// Returns the key defined by `content`, if it is a `key: value` pair.
+fn yaml_key(content: &str) -> Option<&str> {
+    if content.starts_with('{') || content.starts_with('[') {
+        return None;
+    }
+    for quote in &['"', '\''] {
+        if let Some(quoted) = content.strip_prefix(*quote) {
+            let end = quoted.find(*quote)?;
+            let after = &quoted[end + 1..];
+            return if after.starts_with(':') {
+                Some(&quoted[..end])
+            } else {
+                None
+            };
+        }
+    }
+
+    let colon = content
+        .char_indices()
+        .find(|&(index, c)| {
+            c == ':'
+                && content[index + 1..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        })
+        .map(|(index, _)| index)?;
+    let key = content[..colon].trim();
+    if key.is_empty() || key.contains(" #") {
+        None
+    } else {
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn duplicate(line: usize, key: &str, first_line: usize) -> (usize, String) {
+        (
+            line,
+            format!("duplicate {}, first defined on line {}", key, first_line),
+        )
+    }
+
+    #[test]
+    fn json() {
+        let contents = "{\n  \"a\": 1,\n  \"b\": {\"a\": [\"a\", {\"a\": 2}]},\n  \"a\": 3\n}\n";
+        assert_eq!(
+            JsonCheck.check("a.json", contents),
+            [duplicate(4, "key `a`", 2)]
+        );
+        assert!(JsonCheck
+            .check("a.json", "{\"a\": \"\\\"a\\\"\"}")
+            .is_empty());
+        let errors = JsonCheck.check("a.json", "{\n\"a\": }");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.starts_with("invalid JSON"));
+    }
+
+    #[test]
+    fn toml_line_kinds() {
+        let contents = "[package]\nname = \"a\" # [b]\nc = [\n  # note\n  \"]\",\n]\n\n\
+                        d = \"\"\"\n[e]\n\"\"\"\n[[bin]]\n";
+        let kinds = toml_lines(contents)
+            .into_iter()
+            .map(|line| match line {
+                TomlLine::Table { name, is_array } => format!("table {} {}", name, is_array),
+                TomlLine::Key(key) => format!("key {}", key),
+                TomlLine::Continuation => String::from("continuation"),
+                TomlLine::Other => String::from("other"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                "table package false",
+                "key name",
+                "key c",
+                "continuation",
+                "continuation",
+                "continuation",
+                "other",
+                "key d",
+                "continuation",
+                "continuation",
+                "table bin true",
+            ]
+        );
+    }
+
+    #[test]
+    fn toml() {
+        let contents =
+            "a = 1\n[b]\nc = [\n  \"c = 1\",\n]\nc = 2\n[b]\n[[d]]\ne = 1\n[[d]]\ne = 1\n";
+        assert_eq!(
+            TomlCheck.check("a.toml", contents),
+            [
+                duplicate(6, "key `c`", 3),
+                (
+                    7,
+                    String::from("duplicate table `[b]`, first defined on line 2")
+                ),
+            ]
+        );
+        let errors = TomlCheck.check("a.toml", "a = \n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.starts_with("invalid TOML"));
+    }
+
+    #[test]
+    fn yaml() {
+        let contents = "a: 1\n\
+                        b:\n  c: |\n    c: 1\n  c: 2\n\
+                        d:\n  - e: 1\n    f: 1\n  - e: 2\n\
+                        ---\n\
+                        a: 2\n\
+                        a: 3\n";
+        assert_eq!(
+            YamlCheck.check("a.yml", contents),
+            [duplicate(5, "key `c`", 3), duplicate(12, "key `a`", 11)]
+        );
+        let errors = YamlCheck.check("a.yml", "a: [\n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.starts_with("invalid YAML"));
+    }
+}
@@ -0,0 +1,160 @@
+use super::{extension, FileCheck, TidyConfig};
+
+const MPL: &str = "This Source Code Form is subject to the terms of the Mozilla Public \
+License, v. 2.0. If a copy of the MPL was not distributed with this \
+file, You can obtain one at https://mozilla.org/MPL/2.0/.";
+
+const OLD_MPL: &str = "This Source Code Form is subject to the terms of the Mozilla Public \
+License, v. 2.0. If a copy of the MPL was not distributed with this \
+file, You can obtain one at http://mozilla.org/MPL/2.0/.";
+
+const APACHE: &str = "Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or \
+http://www.apache.org/licenses/LICENSE-2.0> or the MIT license \
+<LICENSE-MIT or http://opensource.org/licenses/MIT>, at your \
+option. This file may not be copied, modified, or distributed \
+except according to those terms.";
+
+/// Apache licensed files must also point to where the copyright is held.
+const COPYRIGHT: &[&str] = &[
+    "See the COPYRIGHT file at the top-level directory of this distribution",
+    "See http://rust-lang.org/COPYRIGHT",
+];
+
+const COMMENTS: &[&str] = &["// ", "# ", " *", "/* "];
+
+/// Data files, which have no comments to hold a license header.
+const UNLICENSED_EXTENSIONS: &[&str] = &["yml", "yaml", "toml", "lock", "json", "html", "ini"];
+
+/// Checks that source files start with the MPL, or the Apache
+/// license along with a copyright notice.
+///
+/// A file may opt out by including `xfail-license` in its header.
+pub struct LicenseCheck<'a> {
+    pub config: &'a TidyConfig,
+}
+
+impl FileCheck for LicenseCheck<'_> {
+    fn name(&self) -> &'static str {
+        "license"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        !self.config.skip_check_licenses && !UNLICENSED_EXTENSIONS.contains(&extension(path))
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+        let lines = contents.lines().collect::<Vec<_>>();
+
+        let has_shebang = lines.first().is_some_and(|line| line.starts_with("#!"));
+        if has_shebang && lines.get(1).is_some_and(|line| !line.trim().is_empty()) {
+            errors.push((1, String::from("missing blank line after shebang")));
+        }
+
+        // The license block ends at the first blank line, ignoring the
+        // blank line which follows a shebang.
+        let max_blank_lines = if has_shebang { 2 } else { 1 };
+        let mut blank_lines = 0;
+        let mut license_block = vec![];
+        for line in &lines {
+            if line.trim().is_empty() {
+                blank_lines += 1;
+                if blank_lines >= max_blank_lines {
+                    break;
+                }
+                continue;
+            }
+            if let Some(line) = uncomment(line) {
+                license_block.push(line);
+            }
+        }
+        let header = license_block.join(" ");
+
+        let valid_license =
+            header.contains(OLD_MPL) || header.contains(MPL) || is_apache_licensed(&header);
+        let acknowledged_bad_license = header.contains("xfail-license");
+        if !(valid_license || acknowledged_bad_license) {
+            errors.push((1, String::from("incorrect license")));
+        }
+
+        errors
+    }
+}
+
+/// Returns the text of `line` if it is a comment.
+fn uncomment(line: &str) -> Option<&str> {
+    COMMENTS.iter().find_map(|comment| {
+        if !line.starts_with(comment) {
+            return None;
+        }
+        let text = &line[comment.len()..];
+
+        Some(text.trim_end_matches("*/").trim())
+    })
+}
+
+fn is_apache_licensed(header: &str) -> bool {
+    header.contains(APACHE) && COPYRIGHT.iter().any(|copyright| header.contains(copyright))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(path: &str, contents: &str) -> Vec<(usize, String)> {
+        let config = TidyConfig::from_toml("");
+        LicenseCheck { config: &config }.check(path, contents)
+    }
+
+    const MPL_HEADER: &str =
+        "/* This Source Code Form is subject to the terms of the Mozilla Public\n \
+        * License, v. 2.0. If a copy of the MPL was not distributed with this\n \
+        * file, You can obtain one at https://mozilla.org/MPL/2.0/. */\n";
+
+    #[test]
+    fn licensed_files() {
+        assert!(check("lib.rs", &format!("{}\nfn main() {{}}\n", MPL_HEADER)).is_empty());
+        let python = "#!/usr/bin/env python\n\n\
+                      # This Source Code Form is subject to the terms of the Mozilla Public\n\
+                      # License, v. 2.0. If a copy of the MPL was not distributed with this\n\
+                      # file, You can obtain one at http://mozilla.org/MPL/2.0/.\n";
+        assert!(check("mach.py", python).is_empty());
+        let apache = "// See the COPYRIGHT file at the top-level directory of this distribution\n\
+                      // Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or\n\
+                      // http://www.apache.org/licenses/LICENSE-2.0> or the MIT license\n\
+                      // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your\n\
+                      // option. This file may not be copied, modified, or distributed\n\
+                      // except according to those terms.\n";
+        assert!(check("lib.rs", apache).is_empty());
+        assert!(check("lib.rs", "// xfail-license\n").is_empty());
+    }
+
+    #[test]
+    fn unlicensed_files() {
+        let incorrect = vec![(1, String::from("incorrect license"))];
+        assert_eq!(check("lib.rs", "fn main() {}\n"), incorrect);
+        // The license must be in the first block of comments
+        assert_eq!(
+            check("lib.rs", &format!("// Hello\n\n{}", MPL_HEADER)),
+            incorrect
+        );
+        assert_eq!(
+            check(
+                "build.sh",
+                &format!("#!/bin/sh\n{}", MPL_HEADER.replace("/*", "#"))
+            ),
+            vec![(1, String::from("missing blank line after shebang"))]
+        );
+    }
+
+    #[test]
+    fn data_files_and_config() {
+        let checker = |config: &str, path: &str| {
+            let config = TidyConfig::from_toml(config);
+            LicenseCheck { config: &config }.applies_to(path)
+        };
+        assert!(checker("", "lib.rs"));
+        assert!(!checker("", "Cargo.toml"));
+        assert!(!checker("[configs]\nskip-check-licenses = true", "lib.rs"));
+    }
+}
@@ -0,0 +1,164 @@
+use super::{extension, FileCheck, TidyConfig};
+
+/// Files whose lines are not limited in length.
+const UNLIMITED_LENGTH_EXTENSIONS: &[&str] =
+    &["yml", "yaml", "lock", "json", "html", "toml", "ini"];
+
+/// Checks that lines are at most 120 characters long, or 80 in shell scripts.
+///
+/// Lines containing a URL, and Rust `use` statements without braces,
+/// can't be split and are allowed to be longer.
+pub struct LengthCheck<'a> {
+    pub config: &'a TidyConfig,
+}
+
+impl FileCheck for LengthCheck<'_> {
+    fn name(&self) -> &'static str {
+        "length"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        !self.config.skip_check_length && !UNLIMITED_LENGTH_EXTENSIONS.contains(&extension(path))
+    }
+
+    fn check(&self, path: &str, contents: &str) -> Vec<(usize, String)> {
+        // Prefer shorter lines when shell scripting.
+        let max_length = if extension(path) == "sh" { 80 } else { 120 };
+
+        contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.chars().count() > max_length && !is_unsplittable(path, line))
+            .map(|(index, _)| {
+                (
+                    index + 1,
+                    format!("Line is longer than {} characters", max_length),
+                )
+            })
+            .collect()
+    }
+}
+
+fn is_unsplittable(path: &str, line: &str) -> bool {
+    let contains_url = line.contains("http://") || line.contains("https://");
+    let is_use = extension(path) == "rs" && line.starts_with("use ") && !line.contains('{');
+
+    contains_url || is_use
+}
+
+/// Checks for trailing whitespace, tabs, carriage returns and
+/// a missing newline at the end of the file.
+pub struct WhitespaceCheck;
+
+impl FileCheck for WhitespaceCheck {
+    fn name(&self) -> &'static str {
+        "whitespace"
+    }
+
+    fn applies_to(&self, _path: &str) -> bool {
+        true
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+        for (index, line) in contents.split_inclusive('\n').enumerate() {
+            let line_number = index + 1;
+            let line = match line.strip_suffix('\n') {
+                Some(line) => line,
+                None => {
+                    errors.push((line_number, String::from("no newline at EOF")));
+                    line
+                }
+            };
+            if line.ends_with(' ') {
+                errors.push((line_number, String::from("trailing whitespace")));
+            }
+            if line.contains('\t') {
+                errors.push((line_number, String::from("tab on line")));
+            }
+            if line.contains('\r') {
+                errors.push((line_number, String::from("CR on line")));
+            }
+        }
+
+        errors
+    }
+
+    /// Removes trailing whitespace, including the CR of CRLF line endings,
+    /// and adds a newline to the end of the file. Tabs elsewhere in a line
+    /// are left alone, since there's no way to know how wide they should be.
+    fn fix(&self, _path: &str, contents: &str) -> Option<String> {
+        if contents.is_empty() {
+            return None;
+        }
+
+        let mut fixed = contents
+            .split_inclusive('\n')
+            .map(|line| {
+                let line = line.strip_suffix('\n').unwrap_or(line);
+                line.trim_end_matches(&[' ', '\t', '\r'][..])
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        fixed.push('\n');
+
+        Some(fixed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_length() {
+        let config = TidyConfig::from_toml("");
+        let check = LengthCheck { config: &config };
+        let line = |length: usize| "a".repeat(length);
+        let too_long = |length: usize| (1, format!("Line is longer than {} characters", length));
+
+        assert!(check.check("lib.rs", &line(120)).is_empty());
+        assert_eq!(check.check("lib.rs", &line(121)), [too_long(120)]);
+        assert!(check.check("build.sh", &line(80)).is_empty());
+        assert_eq!(check.check("build.sh", &line(81)), [too_long(80)]);
+
+        let url = format!("// https://servo.org/{}", line(120));
+        assert!(check.check("lib.rs", &url).is_empty());
+        let use_statement = format!("use {};", line(120));
+        assert!(check.check("lib.rs", &use_statement).is_empty());
+        let use_list = format!("use a::{{{}}};", line(120));
+        assert_eq!(check.check("lib.rs", &use_list), [too_long(120)]);
+
+        assert!(!check.applies_to("Cargo.lock"));
+        let config = TidyConfig::from_toml("[configs]\nskip-check-length = true");
+        assert!(!LengthCheck { config: &config }.applies_to("lib.rs"));
+    }
+
+    #[test]
+    fn whitespace() {
+        let check = WhitespaceCheck;
+        assert!(check.check("lib.rs", "fn main() {}\n").is_empty());
+        assert_eq!(
+            check.check("lib.rs", "a \n\tb\r\nc"),
+            [
+                (1, String::from("trailing whitespace")),
+                (2, String::from("tab on line")),
+                (2, String::from("CR on line")),
+                (3, String::from("no newline at EOF")),
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_fix() {
+        let check = WhitespaceCheck;
+        assert_eq!(
+            check.fix("lib.rs", "a \t\r\n\tb\nc").as_deref(),
+            Some("a\n\tb\nc\n")
+        );
+        assert_eq!(check.fix("lib.rs", ""), None);
+
+        let fixed = check.fix("lib.rs", "a \r\nb").unwrap_or_default();
+        assert!(check.check("lib.rs", &fixed).is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+//! A port of Servo's tidy, the style checks run by `test-tidy`.
+//!
+//! Checks implement `FileCheck` and are listed in `file_checks`. Each file
+//! in the repository is read once and passed to every check which applies
+//! to it, with the files spread across a thread per core.
+
+use crate::MarsError;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+};
+
+mod config;
+pub use config::TidyConfig;
+
+mod commits;
+pub use commits::check_commits;
+
+mod cargo_lock;
+mod cargo_toml;
+mod data_files;
+mod license;
+mod lines;
+mod rust;
+mod webidl;
+mod wpt;
+
+/// The extensions of the files tidy checks.
+const FILE_EXTENSIONS_TO_CHECK: &[&str] = &[
+    "rs", "rc", "cpp", "c", "h", "lock", "py", "sh", "toml", "webidl", "json", "html", "yml",
+    "yaml", "ini",
+];
+
+/// Files which are never checked, even though their extension is.
+const FILE_NAMES_TO_IGNORE: &[&str] = &["fake-ld.sh"];
+
+/// A problem found by tidy.
+pub struct TidyError {
+    /// Relative to the repo root, using `/` as a separator.
+    pub path: String,
+    /// The 1 based line number the problem is on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TidyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line, self.message)
+    }
+}
+
+/// A check run on the contents of each file it applies to.
+pub trait FileCheck: Sync {
+    /// The name used to pick the check with `--only`.
+    fn name(&self) -> &'static str;
+
+    /// `path` is relative to the repo root, using `/` as a separator.
+    fn applies_to(&self, path: &str) -> bool;
+
+    /// Returns the problems found in `contents`, as pairs
+    /// of a 1 based line number and a message.
+    fn check(&self, path: &str, contents: &str) -> Vec<(usize, String)>;
+
+    /// Returns `contents` with the problems this check finds fixed, or
+    /// `None` if this check can't fix anything. Only used with `--fix`.
+    fn fix(&self, _path: &str, _contents: &str) -> Option<String> {
+        None
+    }
+}
+
+fn file_checks<'a>(repo_root: &Path, config: &'a TidyConfig) -> Vec<Box<dyn FileCheck + 'a>> {
+    vec![
+        Box::new(license::LicenseCheck { config }),
+        Box::new(lines::LengthCheck { config }),
+        Box::new(lines::WhitespaceCheck),
+        Box::new(rust::UseOrderCheck { config }),
+        Box::new(cargo_lock::CargoLockCheck { config }),
+        Box::new(webidl::WebIdlSpecCheck),
+        Box::new(webidl::DomStructCheck::new(repo_root)),
+        Box::new(data_files::JsonCheck),
+        Box::new(data_files::TomlCheck),
+        Box::new(data_files::YamlCheck),
+        Box::new(cargo_toml::CargoTomlCheck),
+        Box::new(wpt::WptMetadataCheck),
+    ]
+}
+
+/// The outcome of running tidy over the repository.
+pub struct TidyResults {
+    pub errors: Vec<TidyError>,
+    /// The files which were rewritten by `--fix`.
+    pub fixed: Vec<String>,
+}
+
+/// Run the checks over every file in the repository which is neither
+/// ignored by git nor in the ignore lists of `servo-tidy.toml`.
+///
+/// If `only` isn't empty just the checks it names are run. With `fix`
+/// set, problems which can be fixed automatically are fixed in place,
+/// and only the problems which remain are returned.
+pub fn scan(repo_root: &Path, only: &[String], fix: bool) -> Result<TidyResults, MarsError> {
+    let config = TidyConfig::new(repo_root)?;
+    let mut checks = file_checks(repo_root, &config);
+    for name in only {
+        if !checks.iter().any(|check| check.name() == name) {
+            return Err(MarsError::Config(format!(
+                "Unknown tidy check `{}`, expected one of {}.",
+                name,
+                checks
+                    .iter()
+                    .map(|check| check.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+    }
+    if !only.is_empty() {
+        checks.retain(|check| only.iter().any(|name| name == check.name()));
+    }
+
+    let files = list_files(repo_root)?
+        .into_iter()
+        .filter(|path| should_check(&config, path))
+        .collect::<Vec<_>>();
+
+    let threads = thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1);
+    let chunk_size = (files.len() / threads).max(1);
+    let results = thread::scope(|scope| {
+        let handles = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let checks = &checks;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|path| check_file(repo_root, path, checks, fix))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("tidy thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut tidy_results = TidyResults {
+        errors: vec![],
+        fixed: vec![],
+    };
+    for result in results {
+        let (errors, fixed) = result?;
+        tidy_results.errors.extend(errors);
+        tidy_results.fixed.extend(fixed);
+    }
+    tidy_results
+        .errors
+        .sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+
+    Ok(tidy_results)
+}
+
+/// Returns the files git knows about, or would if they were added,
+/// relative to the repo root.
+fn list_files(repo_root: &Path) -> Result<Vec<String>, MarsError> {
+    let output = Command::new("git")
+        .args([
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ])
+        .current_dir(repo_root)
+        .output()
+        .map_err(|error| MarsError::Io {
+            context: String::from("run git ls-files"),
+            error,
+        })?;
+    if !output.status.success() {
+        return Err(MarsError::ChildFailed {
+            command: String::from("git ls-files"),
+            code: output.status.code(),
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+fn should_check(config: &TidyConfig, path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    FILE_EXTENSIONS_TO_CHECK.contains(&extension(path))
+        && !FILE_NAMES_TO_IGNORE.contains(&file_name)
+        // Emacs lock files
+        && !file_name.starts_with(".#")
+        && !config.is_ignored(path)
+}
+
+/// Runs `checks` on the file at `path`, fixing it first if `fix` is set.
+///
+/// Returns the problems found and, if the file was fixed, its path.
+fn check_file(
+    repo_root: &Path,
+    path: &str,
+    checks: &[Box<dyn FileCheck + '_>],
+    fix: bool,
+) -> Result<(Vec<TidyError>, Option<String>), MarsError> {
+    let checks = checks
+        .iter()
+        .filter(|check| check.applies_to(path))
+        .collect::<Vec<_>>();
+    if checks.is_empty() {
+        return Ok((vec![], None));
+    }
+
+    let full_path: PathBuf = repo_root.join(path);
+    let mut contents = match fs::read(&full_path) {
+        Ok(contents) => match String::from_utf8(contents) {
+            Ok(contents) => contents,
+            Err(_) => {
+                return Ok((
+                    vec![TidyError {
+                        path: String::from(path),
+                        line: 1,
+                        message: String::from("file is not valid UTF-8"),
+                    }],
+                    None,
+                ))
+            }
+        },
+        // Deleted files are still listed by git until the deletion is staged
+        Err(_) => return Ok((vec![], None)),
+    };
+
+    let mut fixed = None;
+    if fix {
+        let original = contents.clone();
+        for check in &checks {
+            if let Some(fixed_contents) = check.fix(path, &contents) {
+                contents = fixed_contents;
+            }
+        }
+        if contents != original {
+            fs::write(&full_path, &contents).map_err(|error| MarsError::Io {
+                context: format!("write {}", full_path.display()),
+                error,
+            })?;
+            fixed = Some(String::from(path));
+        }
+    }
+
+    let errors = checks
+        .iter()
+        .flat_map(|check| check.check(path, &contents))
+        .map(|(line, message)| TidyError {
+            path: String::from(path),
+            line,
+            message,
+        })
+        .collect();
+
+    Ok((errors, fixed))
+}
+
+/// Returns the extension of `path`, or an empty string if it has none.
+fn extension(path: &str) -> &str {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match file_name.rfind('.') {
+        Some(dot) => &file_name[dot + 1..],
+        None => "",
+    }
+}
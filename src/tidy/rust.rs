@@ -0,0 +1,333 @@
+use super::{extension, FileCheck, TidyConfig};
+use std::cmp::Ordering;
+
+/// Checks that consecutive `use` statements, and the imports inside
+/// their braces, are in the order rustfmt puts them in.
+///
+/// A block of `use` statements ends at the first line which isn't part
+/// of a `use` statement, so blank lines, comments and attributes can be
+/// used to group them.
+pub struct UseOrderCheck<'a> {
+    pub config: &'a TidyConfig,
+}
+
+impl FileCheck for UseOrderCheck<'_> {
+    fn name(&self) -> &'static str {
+        "use-order"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        self.config.check_alphabetical_order && extension(path) == "rs"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+        let lines = contents.lines().collect::<Vec<_>>();
+
+        // The indent and tree of the previous use statement in the block
+        let mut previous_use: Option<(usize, UseTree)> = None;
+        let mut index = 0;
+        while index < lines.len() {
+            let line = lines[index];
+            let line_number = index + 1;
+            let trimmed = line.trim_start();
+            let indent = line.len() - trimmed.len();
+            if !trimmed.starts_with("use ") {
+                previous_use = None;
+                index += 1;
+                continue;
+            }
+
+            // Join use statements which span multiple lines
+            let mut statement = String::from(strip_comment(trimmed));
+            while !statement.ends_with(';') && index + 1 < lines.len() {
+                index += 1;
+                statement.push(' ');
+                statement.push_str(strip_comment(lines[index]));
+            }
+            let tree = UseTree::parse(&normalize_use(&statement));
+
+            check_lists(&tree, line_number, &mut errors);
+            if let Some((previous_indent, previous_tree)) = &previous_use {
+                if *previous_indent == indent && tree.cmp(previous_tree) == Ordering::Less {
+                    errors.push((
+                        line_number,
+                        format!(
+                            "use statement is not in alphabetical order, expected `{}` before `{}`",
+                            tree.display(),
+                            previous_tree.display()
+                        ),
+                    ));
+                }
+            }
+            previous_use = Some((indent, tree));
+            index += 1;
+        }
+
+        errors
+    }
+}
+
+/// Returns `line` without any `//` comment at its end, or surrounding whitespace.
+fn strip_comment(line: &str) -> &str {
+    line.split("//").next().unwrap_or_default().trim()
+}
+
+/// Strips `use` and `;` from `statement`, along with the whitespace
+/// and trailing commas which rustfmt adds to imports split over lines.
+fn normalize_use(statement: &str) -> String {
+    let statement = statement
+        .trim_start_matches("use ")
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    statement
+        .replace("{ ", "{")
+        .replace(" }", "}")
+        .replace(", ", ",")
+        .replace(",}", "}")
+}
+
+/// Reports braces around a single import, and unordered
+/// imports inside braces, anywhere in `tree`.
+fn check_lists(tree: &UseTree, line_number: usize, errors: &mut Vec<(usize, String)>) {
+    for segment in &tree.segments {
+        let items = match segment {
+            UseSegment::List(items) => items,
+            _ => continue,
+        };
+        if items.len() == 1 && items[0].text != "self" {
+            errors.push((
+                line_number,
+                String::from("use statement contains braces for single import"),
+            ));
+        }
+        for pair in items.windows(2) {
+            if pair[1].cmp(&pair[0]) == Ordering::Less {
+                errors.push((
+                    line_number,
+                    format!(
+                        "use statement contains unordered imports, expected `{}` before `{}`",
+                        pair[1].display(),
+                        pair[0].display()
+                    ),
+                ));
+            }
+        }
+        for item in items {
+            check_lists(item, line_number, errors);
+        }
+    }
+}
+
+/// A parsed import, such as `std::{fs, io::Read}`.
+struct UseTree {
+    segments: Vec<UseSegment>,
+    text: String,
+}
+
+enum UseSegment {
+    /// A name, along with what it is renamed to with `as`.
+    Ident(String, Option<String>),
+    Glob,
+    List(Vec<UseTree>),
+}
+
+impl UseTree {
+    /// Parses an import which has been through `normalize_use`.
+    fn parse(text: &str) -> Self {
+        let segments = split_top_level(text, "::")
+            .into_iter()
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                if segment == "*" {
+                    UseSegment::Glob
+                } else if segment.starts_with('{') {
+                    let inner = segment.trim_start_matches('{');
+                    let inner = inner.strip_suffix('}').unwrap_or(inner);
+                    UseSegment::List(
+                        split_top_level(inner, ",")
+                            .into_iter()
+                            .filter(|item| !item.is_empty())
+                            .map(UseTree::parse)
+                            .collect(),
+                    )
+                } else {
+                    let mut parts = segment.splitn(2, " as ");
+                    let name = String::from(parts.next().unwrap_or_default());
+                    UseSegment::Ident(name, parts.next().map(String::from))
+                }
+            })
+            .collect();
+
+        UseTree {
+            segments,
+            text: String::from(text),
+        }
+    }
+
+    fn display(&self) -> String {
+        self.text.replace(',', ", ")
+    }
+
+    /// Compares imports in the same way rustfmt does when sorting them.
+    fn cmp(&self, other: &UseTree) -> Ordering {
+        for (a, b) in self.segments.iter().zip(&other.segments) {
+            let ordering = a.cmp(b);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        self.segments.len().cmp(&other.segments.len())
+    }
+}
+
+impl UseSegment {
+    fn rank(&self) -> u8 {
+        match self {
+            UseSegment::Ident(name, _) if name == "self" => 0,
+            UseSegment::Ident(name, _) if name == "super" => 1,
+            UseSegment::Ident(name, _) if name == "crate" => 2,
+            UseSegment::Ident(..) => 3,
+            UseSegment::Glob => 4,
+            UseSegment::List(_) => 5,
+        }
+    }
+
+    fn cmp(&self, other: &UseSegment) -> Ordering {
+        match (self, other) {
+            (UseSegment::Ident(a, a_alias), UseSegment::Ident(b, b_alias))
+                if self.rank() == other.rank() =>
+            {
+                // snake_case < CamelCase < UPPER_SNAKE_CASE
+                ident_case(a)
+                    .cmp(&ident_case(b))
+                    .then_with(|| a.cmp(b))
+                    .then_with(|| a_alias.cmp(b_alias))
+            }
+            (UseSegment::List(a), UseSegment::List(b)) => {
+                for (a, b) in a.iter().zip(b) {
+                    let ordering = a.cmp(b);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+
+                a.len().cmp(&b.len())
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+fn ident_case(ident: &str) -> u8 {
+    let is_upper_snake_case = ident
+        .chars()
+        .all(|c| c.is_uppercase() || c.is_numeric() || c == '_');
+    if is_upper_snake_case && ident.starts_with(char::is_uppercase) {
+        2
+    } else if ident.starts_with(char::is_uppercase) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Splits `text` on `separator`, ignoring separators inside braces.
+fn split_top_level<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut index = 0;
+    while index < text.len() {
+        if text[index..].starts_with('{') {
+            depth += 1;
+        } else if text[index..].starts_with('}') {
+            depth -= 1;
+        } else if depth == 0 && text[index..].starts_with(separator) {
+            parts.push(&text[start..index]);
+            index += separator.len();
+            start = index;
+            continue;
+        }
+        index += text[index..].chars().next().map_or(1, char::len_utf8);
+    }
+    parts.push(&text[start..]);
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(contents: &str) -> Vec<(usize, String)> {
+        let config = TidyConfig::from_toml("");
+        UseOrderCheck { config: &config }.check("lib.rs", contents)
+    }
+
+    #[test]
+    fn ordered_uses() {
+        let contents = "use crate::a;\n\
+                        use std::{\n    \
+                            fs, // files\n    \
+                            io::{self, Read},\n\
+                        };\n\
+                        use std::{path::Path, process};\n";
+        assert!(check(contents).is_empty());
+    }
+
+    #[test]
+    fn unordered_uses() {
+        assert_eq!(
+            check("use std::io;\nuse std::fs;\n"),
+            [(
+                2,
+                String::from(
+                    "use statement is not in alphabetical order, expected `std::fs` before `std::io`"
+                )
+            )]
+        );
+        assert_eq!(
+            check("use std::{io, fs};\n"),
+            [(
+                1,
+                String::from("use statement contains unordered imports, expected `fs` before `io`")
+            )]
+        );
+        assert_eq!(
+            check("use std::{fs};\n"),
+            [(
+                1,
+                String::from("use statement contains braces for single import")
+            )]
+        );
+        // Blank lines start a new block
+        assert!(check("use std::io;\n\nuse std::fs;\n").is_empty());
+    }
+
+    #[test]
+    fn comments_after_a_use_statement() {
+        let contents = "use std::{fs, io}; // files\nuse crate::a;\n";
+        assert_eq!(
+            check(contents),
+            [(
+                2,
+                String::from(
+                    "use statement is not in alphabetical order, expected `crate::a` before `std::{fs, io}`"
+                )
+            )]
+        );
+        let contents = "use std::{\n    io, // input\n    fs,\n}; // output\nuse url;\n";
+        assert_eq!(
+            check(contents),
+            [(
+                1,
+                String::from("use statement contains unordered imports, expected `fs` before `io`")
+            )]
+        );
+    }
+}
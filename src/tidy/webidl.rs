@@ -0,0 +1,379 @@
+use super::{extension, FileCheck};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Where the WebIDL files for Servo's DOM live, relative to the repo root.
+const WEBIDLS_DIR: &str = "components/script/dom/webidls";
+
+/// The sites spec links in WebIDL files may point to.
+const WEBIDL_STANDARDS: &[&str] = &[
+    "//www.khronos.org/registry/webgl/extensions",
+    "//www.khronos.org/registry/webgl/specs",
+    "//developer.mozilla.org/en-US/docs/Web/API",
+    "//dev.w3.org/2006/webapi",
+    "//dev.w3.org/csswg",
+    "//dev.w3.org/fxtf",
+    "//dvcs.w3.org/hg",
+    "//dom.spec.whatwg.org",
+    "//drafts.csswg.org",
+    "//drafts.css-houdini.org",
+    "//drafts.fxtf.org",
+    "//encoding.spec.whatwg.org",
+    "//fetch.spec.whatwg.org",
+    "//html.spec.whatwg.org",
+    "//url.spec.whatwg.org",
+    "//xhr.spec.whatwg.org",
+    "//w3c.github.io",
+    "//heycam.github.io/webidl",
+    "//webbluetoothcg.github.io/web-bluetooth/",
+    "//svgwg.org/svg2-draft",
+    "//wicg.github.io",
+    "//webaudio.github.io",
+    "//immersive-web.github.io/",
+    "//github.com/immersive-web/webxr-test-api/",
+    "//gpuweb.github.io",
+];
+
+/// Interfaces which aren't from a spec say so instead of linking to one.
+const INTERNAL_INTERFACE: &str = "This interface is entirely internal to Servo";
+
+/// Checks that the comments at the top of each WebIDL file link to the
+/// spec the interface comes from, and that the links are to a known
+/// standards body.
+pub struct WebIdlSpecCheck;
+
+impl FileCheck for WebIdlSpecCheck {
+    fn name(&self) -> &'static str {
+        "webidl-spec"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        path.starts_with(WEBIDLS_DIR) && extension(path) == "webidl"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+        let mut has_spec_link = contents.contains(INTERNAL_INTERFACE);
+
+        // The header is the comments before the first declaration
+        let header = contents.lines().enumerate().take_while(|(_, line)| {
+            let line = line.trim();
+            line.is_empty()
+                || line.starts_with("//")
+                || line.starts_with("/*")
+                || line.starts_with('*')
+        });
+        for (index, line) in header {
+            for url in urls(line) {
+                // The license
+                if url.contains("mozilla.org/MPL/") {
+                    continue;
+                }
+                if WEBIDL_STANDARDS
+                    .iter()
+                    .any(|standard| url.contains(standard))
+                {
+                    has_spec_link = true;
+                } else {
+                    errors.push((
+                        index + 1,
+                        format!(
+                            "Specification link `{}` is not to a known standards body.",
+                            url
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if !has_spec_link && errors.is_empty() {
+            errors.push((1, String::from("No specification link found.")));
+        }
+
+        errors
+    }
+}
+
+/// Checks that the first field of each `#[dom_struct]` holds its parent
+/// type, and is named after it, or is `reflector_: Reflector` for types
+/// without a parent.
+///
+/// The parent of a type is taken from the WebIDL interface of the same
+/// name, when there is one.
+pub struct DomStructCheck {
+    /// The parent of each WebIDL interface, if it has one.
+    parents: HashMap<String, Option<String>>,
+}
+
+impl DomStructCheck {
+    pub fn new(repo_root: &Path) -> Self {
+        let mut parents = HashMap::new();
+        if let Ok(entries) = fs::read_dir(repo_root.join(WEBIDLS_DIR)) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                if let Ok(contents) = fs::read_to_string(entry.path()) {
+                    parents.extend(interfaces(&contents));
+                }
+            }
+        }
+
+        DomStructCheck { parents }
+    }
+}
+
+impl FileCheck for DomStructCheck {
+    fn name(&self) -> &'static str {
+        "dom-struct"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        path.starts_with("components/script/") && extension(path) == "rs"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+        let lines = contents.lines().collect::<Vec<_>>();
+
+        for (index, line) in lines.iter().enumerate() {
+            if line.trim() != "#[dom_struct]" {
+                continue;
+            }
+
+            // Skip over other attributes, and comments, to the struct
+            let mut following = lines
+                .iter()
+                .enumerate()
+                .skip(index + 1)
+                .map(|(index, line)| (index, line.trim()))
+                .filter(|(_, line)| !line.starts_with("#[") && !line.starts_with("//"));
+            let (struct_index, struct_line) = match following.next() {
+                Some(line) => line,
+                None => break,
+            };
+            let name = match struct_name(struct_line) {
+                Some(name) => name,
+                None => {
+                    errors.push((
+                        struct_index + 1,
+                        String::from("#[dom_struct] must be followed by a struct"),
+                    ));
+                    continue;
+                }
+            };
+
+            let expected = match self.parents.get(name) {
+                Some(Some(parent)) => Some((parent.to_lowercase(), parent.clone())),
+                Some(None) => Some((String::from("reflector_"), String::from("Reflector"))),
+                // Without an interface the parent isn't known, so any field
+                // named after its type, or a reflector, is accepted
+                None => None,
+            };
+
+            let (field_index, field) = match following.next() {
+                Some((field_index, field)) if !field.starts_with('}') => (field_index, field),
+                _ => {
+                    errors.push((
+                        struct_index + 1,
+                        format!(
+                            "dom_struct `{}` must have its parent type as its first field",
+                            name
+                        ),
+                    ));
+                    continue;
+                }
+            };
+            let field = field
+                .trim_start_matches("pub(crate) ")
+                .trim_start_matches("pub ")
+                .trim_end_matches(',');
+            let (field_name, field_type) = match field.split_once(':') {
+                Some((field_name, field_type)) => (field_name.trim(), field_type.trim()),
+                None => continue,
+            };
+
+            let is_valid = match &expected {
+                Some((expected_name, expected_type)) => {
+                    field_name == expected_name && field_type == expected_type
+                }
+                None => {
+                    field_name == field_type.to_lowercase()
+                        || (field_name == "reflector_" && field_type == "Reflector")
+                }
+            };
+            if !is_valid {
+                let expected = match expected {
+                    Some((expected_name, expected_type)) => {
+                        format!("`{}: {}`", expected_name, expected_type)
+                    }
+                    None => String::from("its parent type, named after the type"),
+                };
+                errors.push((
+                    field_index + 1,
+                    format!(
+                        "the first field of dom_struct `{}` should be {}, found `{}: {}`",
+                        name, expected, field_name, field_type
+                    ),
+                ));
+            }
+        }
+
+        errors
+    }
+}
+
+/// Returns the URLs in `line`.
+fn urls(line: &str) -> Vec<&str> {
+    let mut urls = vec![];
+    let mut rest = line;
+    while let Some(start) = rest.find("http") {
+        let url = &rest[start..];
+        let end = url
+            .find(|c: char| c.is_whitespace() || c == '>' || c == ')')
+            .unwrap_or(url.len());
+        let url = &url[..end];
+        if url.starts_with("http://") || url.starts_with("https://") {
+            urls.push(url);
+        }
+        rest = &rest[start + end.max(1)..];
+    }
+
+    urls
+}
+
+/// Returns the interfaces declared in a WebIDL file, along with their parents.
+fn interfaces(contents: &str) -> Vec<(String, Option<String>)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut line = line.trim();
+            // [Exposed=Window] interface Foo {
+            if line.starts_with('[') {
+                line = line.split_once(']').map_or("", |(_, rest)| rest.trim());
+            }
+            let declaration = line.strip_prefix("interface ")?;
+            if declaration.starts_with("mixin ") {
+                return None;
+            }
+            let declaration = declaration.split('{').next().unwrap_or_default();
+            let (name, parent) = match declaration.split_once(':') {
+                Some((name, parent)) => (name.trim(), Some(String::from(parent.trim()))),
+                None => (declaration.trim(), None),
+            };
+
+            Some((String::from(name), parent))
+        })
+        .collect()
+}
+
+/// Returns the name of the struct declared on `line`, such as `Node`
+/// from `pub struct Node {`.
+fn struct_name(line: &str) -> Option<&str> {
+    let line = line
+        .trim_start_matches("pub(crate) ")
+        .trim_start_matches("pub ");
+    let name = line.strip_prefix("struct ")?;
+    let end = name
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(name.len());
+
+    Some(&name[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_links() {
+        let check = |contents: &str| WebIdlSpecCheck.check("Node.webidl", contents);
+        let license = "/* This Source Code Form is subject to the terms of the Mozilla Public\n \
+                       * License, v. 2.0. If a copy of the MPL was not distributed with this\n \
+                       * file, You can obtain one at https://mozilla.org/MPL/2.0/. */\n";
+
+        let linked = format!(
+            "{}\n// https://dom.spec.whatwg.org/#interface-node\ninterface Node {{}};\n",
+            license
+        );
+        assert!(check(&linked).is_empty());
+        let internal = format!(
+            "{}\n// {}\ninterface TestBinding {{}};\n",
+            license, INTERNAL_INTERFACE
+        );
+        assert!(check(&internal).is_empty());
+        assert_eq!(
+            check(&format!("{}\ninterface Node {{}};\n", license)),
+            [(1, String::from("No specification link found."))]
+        );
+        assert_eq!(
+            check(&format!(
+                "{}\n// https://example.com/node\ninterface Node {{}};\n",
+                license
+            )),
+            [(
+                5,
+                String::from(
+                    "Specification link `https://example.com/node` is not to a known standards body."
+                )
+            )]
+        );
+        // Links after the header aren't spec links
+        let late_link = "interface Node {};\n// https://dom.spec.whatwg.org/\n";
+        assert_eq!(
+            check(late_link),
+            [(1, String::from("No specification link found."))]
+        );
+    }
+
+    #[test]
+    fn webidl_interfaces() {
+        let contents = "[Exposed=Window]\ninterface Element : Node {\n};\n\
+                        interface mixin ParentNode {};\n\
+                        interface Node {};\n";
+        assert_eq!(
+            interfaces(contents),
+            [
+                (String::from("Element"), Some(String::from("Node"))),
+                (String::from("Node"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn dom_struct_parents() {
+        let check = DomStructCheck {
+            parents: interfaces("interface Element : Node {};\ninterface Node {};\n")
+                .into_iter()
+                .collect(),
+        };
+        let check = |contents: &str| check.check("components/script/dom/element.rs", contents);
+
+        assert!(check("#[dom_struct]\npub struct Element {\n    node: Node,\n}\n").is_empty());
+        assert!(
+            check("#[dom_struct]\npub struct Node {\n    reflector_: Reflector,\n}\n").is_empty()
+        );
+        // Types without an interface only need a field named after its type
+        assert!(check("#[dom_struct]\n#[allow(unused)]\npub struct Attr {\n    // The parent\n    node: Node,\n}\n").is_empty());
+
+        assert_eq!(
+            check("#[dom_struct]\npub struct Element {\n    eventtarget: EventTarget,\n}\n"),
+            [(
+                3,
+                String::from(
+                    "the first field of dom_struct `Element` should be `node: Node`, found `eventtarget: EventTarget`"
+                )
+            )]
+        );
+        assert_eq!(
+            check("#[dom_struct]\npub struct Node {}\n"),
+            [(
+                2,
+                String::from("dom_struct `Node` must have its parent type as its first field")
+            )]
+        );
+        assert_eq!(
+            check("#[dom_struct]\npub enum Node {}\n"),
+            [(
+                2,
+                String::from("#[dom_struct] must be followed by a struct")
+            )]
+        );
+    }
+}
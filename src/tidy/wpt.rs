@@ -0,0 +1,162 @@
+use super::{data_files::DuplicateKeys, extension, FileCheck};
+
+/// Where the web-platform-tests and their expectations live,
+/// relative to the repo root.
+const WPT_DIR: &str = "tests/wpt/";
+
+/// Checks that WPT metadata `.ini` files are well-formed.
+///
+/// The files are made of `[section]` headers, and `key: value` pairs,
+/// each nested under the line before by indenting it two more spaces.
+/// Keys may also come before the first section, such as the `prefs`
+/// of a `__dir__.ini` file.
+/// A key's value can be continued on the lines after it, such as with
+/// the `if condition: value` lines of a conditional expectation.
+pub struct WptMetadataCheck;
+
+impl FileCheck for WptMetadataCheck {
+    fn name(&self) -> &'static str {
+        "wpt-metadata"
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        path.starts_with(WPT_DIR) && extension(path) == "ini"
+    }
+
+    fn check(&self, _path: &str, contents: &str) -> Vec<(usize, String)> {
+        let mut errors = vec![];
+        let mut keys = DuplicateKeys::default();
+        // The indentation of each open section
+        let mut sections: Vec<usize> = vec![];
+        // The indentation of the key whose value may continue
+        let mut value_indent: Option<usize> = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim_start();
+            let indent = line.len() - trimmed.len();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if line[..indent].contains('\t') {
+                errors.push((line_number, String::from("indented with a tab")));
+                continue;
+            }
+            if let Some(key_indent) = value_indent {
+                if indent > key_indent {
+                    continue;
+                }
+                value_indent = None;
+            }
+            if indent % 2 != 0 {
+                errors.push((
+                    line_number,
+                    String::from("indentation is not a multiple of two spaces"),
+                ));
+                continue;
+            }
+
+            let is_section = trimmed.starts_with('[');
+            while sections.last().is_some_and(|&last| last >= indent) {
+                sections.pop();
+                keys.pop();
+            }
+            let expected_indent = sections.last().map_or(0, |last| last + 2);
+            if indent > expected_indent {
+                errors.push((
+                    line_number,
+                    format!("expected an indent of {} spaces", expected_indent),
+                ));
+                continue;
+            }
+
+            if is_section {
+                let name = match trimmed.strip_suffix(']') {
+                    Some(name) => &name[1..],
+                    None => {
+                        errors.push((line_number, String::from("section header is missing `]`")));
+                        continue;
+                    }
+                };
+                keys.section(name, line_number);
+                keys.push();
+                sections.push(indent);
+                continue;
+            }
+
+            match trimmed.split_once(':') {
+                Some((key, _)) if !key.trim().is_empty() => {
+                    keys.key(key.trim(), line_number);
+                    value_indent = Some(indent);
+                }
+                _ => errors.push((line_number, String::from("expected `key: value`"))),
+            }
+        }
+        errors.extend(keys.errors);
+        errors.sort();
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(contents: &str) -> Vec<(usize, String)> {
+        WptMetadataCheck.check("tests/wpt/metadata/dom/__dir__.ini", contents)
+    }
+
+    #[test]
+    fn well_formed_metadata() {
+        let contents = "prefs: [dom.foo:true]\n\
+                        [test.html]\n  \
+                          [subtest]\n    \
+                            expected:\n      \
+                              if os == \"linux\": FAIL\n      \
+                              PASS\n\n\
+                        [other.html]\n  \
+                          expected: TIMEOUT\n";
+        assert!(check(contents).is_empty());
+    }
+
+    #[test]
+    fn malformed_metadata() {
+        assert_eq!(
+            check("[test.html]\n    expected: FAIL\n"),
+            [(2, String::from("expected an indent of 2 spaces"))]
+        );
+        assert_eq!(
+            check("[test.html]\n expected: FAIL\n"),
+            [(
+                2,
+                String::from("indentation is not a multiple of two spaces")
+            )]
+        );
+        assert_eq!(
+            check("[test.html]\n\texpected: FAIL\n"),
+            [(2, String::from("indented with a tab"))]
+        );
+        assert_eq!(
+            check("[test.html\n"),
+            [(1, String::from("section header is missing `]`"))]
+        );
+        assert_eq!(
+            check("prefs\n"),
+            [(1, String::from("expected `key: value`"))]
+        );
+        assert_eq!(
+            check("[test.html]\n  expected: FAIL\n  expected: PASS\n[test.html]\n"),
+            [
+                (
+                    3,
+                    String::from("duplicate key `expected`, first defined on line 2")
+                ),
+                (
+                    4,
+                    String::from("duplicate section `[test.html]`, first defined on line 1")
+                ),
+            ]
+        );
+    }
+}